@@ -0,0 +1,367 @@
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::math::{mul_div, mul_div_ceil, sqrt_mul};
+use crate::state::{AgentChainError, AgentChainState, AgentStrategy};
+
+/// Fixed-point scale of `AmmPool::price_of_a`.
+pub const PRICE_SCALE: u128 = 1_000_000_000;
+
+const BPS: u128 = 10_000;
+
+/// A constant-product pool between two assets, seeded by a MarketMaker agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmmPool {
+    pub id: String,
+    /// The pair's assets, `asset_a < asset_b`.
+    pub asset_a: String,
+    pub asset_b: String,
+    pub reserve_a: u128,
+    pub reserve_b: u128,
+    pub total_shares: u128,
+    /// Fee kept in the pool on every swap, from the creator's `spread_bps`.
+    pub fee_bps: u16,
+    pub creator_agent: String,
+    pub swap_count: u64,
+    pub created_at: u64,
+}
+
+impl AmmPool {
+    /// Units of `asset_b` per unit of `asset_a`, scaled by `PRICE_SCALE`. Saturates
+    /// at `u128::MAX`.
+    pub fn price_of_a(&self) -> u128 {
+        if self.reserve_a == 0 {
+            0
+        } else {
+            mul_div(self.reserve_b, PRICE_SCALE, self.reserve_a).unwrap_or(u128::MAX)
+        }
+    }
+
+    /// Whether `asset` is one of the pool's two assets.
+    pub fn trades(&self, asset: &str) -> bool {
+        asset == self.asset_a || asset == self.asset_b
+    }
+
+    fn reserves_mut(&mut self, asset_in: &str) -> Option<(&mut u128, &mut u128)> {
+        if asset_in == self.asset_a {
+            Some((&mut self.reserve_a, &mut self.reserve_b))
+        } else if asset_in == self.asset_b {
+            Some((&mut self.reserve_b, &mut self.reserve_a))
+        } else {
+            None
+        }
+    }
+
+    /// Output of swapping `amount_in` of `asset_in`, after the fee. `None` if the pool
+    /// does not trade `asset_in` or the reserves would overflow.
+    pub fn quote(&self, asset_in: &str, amount_in: u128) -> Option<u128> {
+        let (reserve_in, reserve_out) = if asset_in == self.asset_a {
+            (self.reserve_a, self.reserve_b)
+        } else if asset_in == self.asset_b {
+            (self.reserve_b, self.reserve_a)
+        } else {
+            return None;
+        };
+        let amount_in = amount_in - mul_div(amount_in, self.fee_bps as u128, BPS)?;
+        let new_reserve_in = reserve_in.checked_add(amount_in)?;
+        if new_reserve_in == 0 {
+            return Some(0);
+        }
+        mul_div(reserve_out, amount_in, new_reserve_in)
+    }
+
+    pub fn other_asset(&self, asset: &str) -> &str {
+        if asset == self.asset_a {
+            &self.asset_b
+        } else {
+            &self.asset_a
+        }
+    }
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    /// Id of the pool between two assets, regardless of their order.
+    pub fn amm_pool_id(asset_x: &str, asset_y: &str) -> String {
+        if asset_x < asset_y {
+            format!("{}/{}", asset_x, asset_y)
+        } else {
+            format!("{}/{}", asset_y, asset_x)
+        }
+    }
+
+    pub fn lp_shares_key(pool_id: &str, agent_id: &str) -> String {
+        format!("{}_{}", pool_id, agent_id)
+    }
+
+    pub async fn get_amm_pool(&self, pool_id: &str) -> Result<AmmPool, AgentChainError> {
+        self.amm_pools
+            .get(pool_id)
+            .await?
+            .ok_or_else(|| AgentChainError::AmmFailed("Pool not found".to_string()))
+    }
+
+    pub async fn lp_shares_of(&self, pool_id: &str, agent_id: &str) -> Result<u128, AgentChainError> {
+        Ok(self
+            .lp_shares
+            .get(&Self::lp_shares_key(pool_id, agent_id))
+            .await?
+            .unwrap_or(0))
+    }
+
+    /// Opens a pool for a MarketMaker agent, seeding it with `liquidity_depth` of
+    /// `seed_asset` and `paired_amount` of `paired_asset`, which sets the initial price.
+    /// A pool whose shares have all been burned can be seeded again the same way.
+    pub async fn create_amm_pool(
+        &mut self,
+        agent_id: &str,
+        seed_asset: String,
+        paired_asset: String,
+        paired_amount: u128,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let Some(AgentStrategy::MarketMaker {
+            spread_bps,
+            liquidity_depth,
+        }) = agent
+            .enabled_strategy("MarketMaker")
+            .map(|slot| slot.strategy.clone())
+        else {
+            return Err(AgentChainError::AmmFailed(
+                "Only agents running a MarketMaker strategy can create pools".to_string(),
+            ));
+        };
+        if seed_asset == paired_asset {
+            return Err(AgentChainError::AmmFailed("Pool assets must differ".to_string()));
+        }
+        if liquidity_depth == 0 || paired_amount == 0 {
+            return Err(AgentChainError::AmmFailed(
+                "Pools must be seeded on both sides".to_string(),
+            ));
+        }
+        let pool_id = Self::amm_pool_id(&seed_asset, &paired_asset);
+        let existing = self.amm_pools.get(&pool_id).await?;
+        if existing.as_ref().is_some_and(|pool| pool.total_shares > 0) {
+            return Err(AgentChainError::AmmFailed(format!("Pool {} already exists", pool_id)));
+        }
+
+        self.record_spending(agent_id, &pool_id, &seed_asset, liquidity_depth, now)
+            .await?;
+        self.record_spending(agent_id, &pool_id, &paired_asset, paired_amount, now)
+            .await?;
+        agent.debit(&seed_asset, liquidity_depth)?;
+        agent.debit(&paired_asset, paired_amount)?;
+        let (asset_a, asset_b, seed_a, seed_b) = if seed_asset < paired_asset {
+            (seed_asset, paired_asset, liquidity_depth, paired_amount)
+        } else {
+            (paired_asset, seed_asset, paired_amount, liquidity_depth)
+        };
+        // Rounding dust left in a drained pool goes to whoever seeds it again.
+        let (dust_a, dust_b) = existing
+            .as_ref()
+            .map_or((0, 0), |pool| (pool.reserve_a, pool.reserve_b));
+        let too_large = || AgentChainError::AmmFailed("Seed amounts are too large".to_string());
+        let reserve_a = seed_a.checked_add(dust_a).ok_or_else(too_large)?;
+        let reserve_b = seed_b.checked_add(dust_b).ok_or_else(too_large)?;
+        let shares = sqrt_mul(reserve_a, reserve_b);
+
+        let pool = AmmPool {
+            id: pool_id.clone(),
+            asset_a,
+            asset_b,
+            reserve_a,
+            reserve_b,
+            total_shares: shares,
+            fee_bps: spread_bps,
+            creator_agent: agent_id.to_string(),
+            swap_count: existing.map_or(0, |pool| pool.swap_count),
+            created_at: now,
+        };
+        self.agents.insert(agent_id, agent)?;
+        self.amm_pools.insert(&pool_id, pool)?;
+        self.lp_shares.insert(&Self::lp_shares_key(&pool_id, agent_id), shares)?;
+        Ok(pool_id)
+    }
+
+    /// Deposits both assets at the pool's current ratio. Only the part of each amount
+    /// that matches the ratio is taken. Returns the shares minted.
+    pub async fn add_liquidity(
+        &mut self,
+        agent_id: &str,
+        pool_id: &str,
+        max_amount_a: u128,
+        max_amount_b: u128,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        let mut pool = self.get_amm_pool(pool_id).await?;
+        if pool.total_shares == 0 || pool.reserve_a == 0 || pool.reserve_b == 0 {
+            return Err(AgentChainError::AmmFailed(
+                "Pool is empty and must be seeded again".to_string(),
+            ));
+        }
+
+        let too_large = || AgentChainError::AmmFailed("Deposit is too large".to_string());
+        let shares = mul_div(max_amount_a, pool.total_shares, pool.reserve_a)
+            .ok_or_else(too_large)?
+            .min(mul_div(max_amount_b, pool.total_shares, pool.reserve_b).ok_or_else(too_large)?);
+        if shares == 0 {
+            return Err(AgentChainError::AmmFailed("Deposit is too small".to_string()));
+        }
+        let amount_a = mul_div_ceil(shares, pool.reserve_a, pool.total_shares).ok_or_else(too_large)?;
+        let amount_b = mul_div_ceil(shares, pool.reserve_b, pool.total_shares).ok_or_else(too_large)?;
+        pool.reserve_a = pool.reserve_a.checked_add(amount_a).ok_or_else(too_large)?;
+        pool.reserve_b = pool.reserve_b.checked_add(amount_b).ok_or_else(too_large)?;
+        pool.total_shares = pool.total_shares.checked_add(shares).ok_or_else(too_large)?;
+
+        self.record_spending(agent_id, pool_id, &pool.asset_a, amount_a, now)
+            .await?;
+        self.record_spending(agent_id, pool_id, &pool.asset_b, amount_b, now)
+            .await?;
+        let mut agent = self.get_agent(agent_id).await?;
+        agent.debit(&pool.asset_a, amount_a)?;
+        agent.debit(&pool.asset_b, amount_b)?;
+        let held = self.lp_shares_of(pool_id, agent_id).await?;
+
+        self.agents.insert(agent_id, agent)?;
+        self.amm_pools.insert(pool_id, pool)?;
+        self.lp_shares
+            .insert(&Self::lp_shares_key(pool_id, agent_id), held + shares)?;
+        Ok(shares)
+    }
+
+    /// Burns LP shares for their part of both reserves. Returns the amounts paid out.
+    pub async fn remove_liquidity(
+        &mut self,
+        agent_id: &str,
+        pool_id: &str,
+        shares: u128,
+    ) -> Result<(u128, u128), AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let mut pool = self.get_amm_pool(pool_id).await?;
+        let held = self.lp_shares_of(pool_id, agent_id).await?;
+        if shares == 0 || shares > held {
+            return Err(AgentChainError::AmmFailed(format!(
+                "Agent holds {} shares, requested {}",
+                held, shares
+            )));
+        }
+
+        let amount_a = mul_div(shares, pool.reserve_a, pool.total_shares).unwrap_or(0);
+        let amount_b = mul_div(shares, pool.reserve_b, pool.total_shares).unwrap_or(0);
+        pool.reserve_a -= amount_a;
+        pool.reserve_b -= amount_b;
+        pool.total_shares -= shares;
+        agent.credit(&pool.asset_a, amount_a);
+        agent.credit(&pool.asset_b, amount_b);
+
+        let key = Self::lp_shares_key(pool_id, agent_id);
+        if held == shares {
+            self.lp_shares.remove(&key)?;
+        } else {
+            self.lp_shares.insert(&key, held - shares)?;
+        }
+        self.agents.insert(agent_id, agent)?;
+        self.amm_pools.insert(pool_id, pool)?;
+        Ok((amount_a, amount_b))
+    }
+
+    /// Swaps `amount_in` of `asset_in` for the pool's other asset. The fee stays in the
+    /// pool for liquidity providers. Fails if the output is below `min_amount_out`.
+    pub async fn swap(
+        &mut self,
+        agent_id: &str,
+        pool_id: &str,
+        asset_in: &str,
+        amount_in: u128,
+        min_amount_out: u128,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        let mut pool = self.get_amm_pool(pool_id).await?;
+        if !pool.trades(asset_in) {
+            return Err(AgentChainError::AmmFailed(format!("Pool does not trade {}", asset_in)));
+        }
+        let too_large = || AgentChainError::AmmFailed("Swap amount is too large".to_string());
+        let amount_out = pool.quote(asset_in, amount_in).ok_or_else(too_large)?;
+        if amount_out == 0 || amount_out < min_amount_out {
+            return Err(AgentChainError::AmmFailed(format!(
+                "Output of {} is below the minimum of {}",
+                amount_out, min_amount_out
+            )));
+        }
+
+        let asset_out = pool.other_asset(asset_in).to_string();
+        if let Some((reserve_in, reserve_out)) = pool.reserves_mut(asset_in) {
+            *reserve_in = reserve_in.checked_add(amount_in).ok_or_else(too_large)?;
+            *reserve_out -= amount_out;
+        }
+
+        self.record_spending(agent_id, pool_id, asset_in, amount_in, now)
+            .await?;
+        let mut agent = self.get_agent(agent_id).await?;
+        agent.debit(asset_in, amount_in)?;
+        agent.credit(&asset_out, amount_out);
+        pool.swap_count += 1;
+
+        self.agents.insert(agent_id, agent)?;
+        self.amm_pools.insert(pool_id, pool)?;
+        Ok(amount_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(reserve_a: u128, reserve_b: u128, fee_bps: u16) -> AmmPool {
+        AmmPool {
+            id: "eth/usdc".to_string(),
+            asset_a: "eth".to_string(),
+            asset_b: "usdc".to_string(),
+            reserve_a,
+            reserve_b,
+            total_shares: 1,
+            fee_bps,
+            creator_agent: "maker".to_string(),
+            swap_count: 0,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn quotes_follow_the_constant_product_after_the_fee() {
+        let pool = pool(1_000, 4_000, 0);
+        assert_eq!(pool.quote("eth", 1_000), Some(2_000));
+        assert_eq!(pool.quote("usdc", 4_000), Some(500));
+
+        // A 1% fee is taken from the input before it reaches the curve.
+        let pool = self::pool(1_000, 4_000, 100);
+        assert_eq!(pool.quote("eth", 1_000), Some(4_000 * 990 / 1_990));
+    }
+
+    #[test]
+    fn quotes_for_other_assets_or_overflowing_reserves_fail() {
+        let pool = pool(1_000, 4_000, 30);
+        assert_eq!(pool.quote("btc", 1), None);
+        assert_eq!(self::pool(u128::MAX, 1, 0).quote("eth", 1), None);
+        assert_eq!(
+            self::pool(2, u128::MAX, 0).quote("eth", 2),
+            Some(u128::MAX / 2)
+        );
+    }
+
+    #[test]
+    fn pools_seeded_with_eighteen_decimal_amounts_get_shares_and_quotes() {
+        let seed = 1_000_000_000_000_000_000_000_000u128;
+        assert_eq!(sqrt_mul(seed, seed), seed);
+        let pool = pool(seed, seed, 0);
+        assert_eq!(pool.quote("eth", seed), Some(seed / 2));
+        assert_eq!(pool.price_of_a(), PRICE_SCALE);
+    }
+
+    #[test]
+    fn prices_saturate_instead_of_overflowing() {
+        assert_eq!(pool(2, 1, 0).price_of_a(), PRICE_SCALE / 2);
+        assert_eq!(pool(0, 1, 0).price_of_a(), 0);
+        assert_eq!(pool(1, u128::MAX, 0).price_of_a(), u128::MAX);
+    }
+}
@@ -1,40 +1,341 @@
 use async_trait::async_trait;
 use linera_sdk::{
-    base::{ApplicationId, ChainId, Timestamp},
+    abis::fungible::{FungibleOperation, FungibleTokenAbi},
+    base::{Account, AccountOwner, Amount, ApplicationId, ChainId, Timestamp},
     Contract, ContractRuntime,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::state::{AgentChainState, AgentStrategy, ServiceStatus, TransactionType};
+use crate::governance::ProposalAction;
+use crate::oracle::FeedValue;
+use crate::orderbook::OrderSide;
+use crate::strategy_app::{CustomStrategyAbi, StrategyRequest};
+use crate::routing::ScoringWeights;
+use crate::state::{
+    Agent, AgentChainConfig, AgentChainState, AgentStrategy, ListingStatus, MarketListing,
+    MilestoneTerms, RevenueSplit, ServiceTerms, SpendingPolicy, StrategySlot,
+    TransactionType, NATIVE_ASSET,
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum Operation {
     CreateAgent {
         name: String,
         description: String,
-        strategy: AgentStrategy,
-        initial_balance: u128,
+        strategies: Vec<StrategySlot>,
     },
     TransferTokens {
+        from_agent: String,
         to_agent: String,
+        asset: String,
         amount: u128,
     },
     RequestService {
+        requester_agent: String,
+        provider_agent: String,
+        service_type: String,
+        parameters: String,
+        asset: String,
+        payment: u128,
+        milestones: Vec<MilestoneTerms>,
+        insured: bool,
+        /// Pay on the provider's credit line to the requester instead of from escrow.
+        on_credit: bool,
+    },
+    RequestBestProvider {
+        requester_agent: String,
+        service_type: String,
+        parameters: String,
+        asset: String,
+        max_price: u128,
+        weights: ScoringWeights,
+        insured: bool,
+    },
+    OpenDispute {
+        request_id: String,
+    },
+    /// Closes a disputed request; only the configured dispute arbiter may sign it.
+    ResolveDispute {
+        request_id: String,
+        requester_wins: bool,
+    },
+    PostRfq {
+        requester_agent: String,
+        service_type: String,
+        parameters: String,
+        asset: String,
+        max_budget: u128,
+        bidding_deadline: u64,
+        insured: bool,
+    },
+    SubmitBid {
+        rfq_id: String,
+        provider_agent: String,
+        price: u128,
+    },
+    AwardRfq {
+        rfq_id: String,
         provider_agent: String,
+    },
+    AutoAwardRfq {
+        rfq_id: String,
+    },
+    CancelRfq {
+        rfq_id: String,
+    },
+    PostJob {
+        requester_agent: String,
         service_type: String,
         parameters: String,
+        asset: String,
         payment: u128,
+        min_reputation: u64,
+        required_stake: u128,
+    },
+    ClaimJob {
+        job_id: String,
+        provider_agent: String,
+    },
+    CancelJob {
+        job_id: String,
+    },
+    /// Moves tokens from the signer into the application and credits them to the agent.
+    Deposit {
+        agent_id: String,
+        asset: String,
+        amount: u128,
+    },
+    Withdraw {
+        agent_id: String,
+        asset: String,
+        amount: u128,
+    },
+    SetRevenueSplit {
+        agent_id: String,
+        split: Option<RevenueSplit>,
+    },
+    SetSpendingPolicy {
+        agent_id: String,
+        policy: SpendingPolicy,
+    },
+    Approve {
+        owner_agent: String,
+        spender_agent: String,
+        asset: String,
+        amount: u128,
+        expires_at: Option<u64>,
+    },
+    TransferFrom {
+        spender_agent: String,
+        from_agent: String,
+        to_agent: String,
+        asset: String,
+        amount: u128,
+    },
+    RevokeAllowance {
+        owner_agent: String,
+        spender_agent: String,
+        asset: String,
     },
     AcceptService {
         request_id: String,
     },
+    SubmitMilestone {
+        request_id: String,
+        milestone_index: u32,
+    },
+    AcceptMilestone {
+        request_id: String,
+        milestone_index: u32,
+    },
+    RejectMilestone {
+        request_id: String,
+        milestone_index: u32,
+    },
     CompleteService {
         request_id: String,
         success: bool,
     },
+    RefundPayment {
+        request_id: String,
+        amount: u128,
+    },
+    DepositToPool {
+        agent_id: String,
+        asset: String,
+        amount: u128,
+    },
+    WithdrawFromPool {
+        agent_id: String,
+        asset: String,
+        shares: u128,
+    },
+    /// Posts `collateral_asset` as collateral for borrowing `asset`.
+    AddCollateral {
+        agent_id: String,
+        asset: String,
+        collateral_asset: String,
+        amount: u128,
+    },
+    RemoveCollateral {
+        agent_id: String,
+        asset: String,
+        amount: u128,
+    },
+    Borrow {
+        agent_id: String,
+        asset: String,
+        amount: u128,
+    },
+    Repay {
+        agent_id: String,
+        asset: String,
+        amount: u128,
+    },
+    Liquidate {
+        liquidator_agent: String,
+        borrower_agent: String,
+        asset: String,
+        amount: u128,
+    },
+    SetCreditLimit {
+        creditor_agent: String,
+        debtor_agent: String,
+        asset: String,
+        limit: u128,
+    },
+    AcceptCreditLimit {
+        creditor_agent: String,
+        debtor_agent: String,
+        asset: String,
+    },
+    /// Bills the requester of a completed credit-backed request for its payment.
+    IssueInvoice {
+        request_id: String,
+        description: String,
+        term_seconds: u64,
+    },
+    SettleInvoice {
+        invoice_id: String,
+    },
+    MarkInvoiceOverdue {
+        invoice_id: String,
+    },
+    RequestRefund {
+        request_id: String,
+        amount: u128,
+        reason: String,
+    },
+    ApproveRefund {
+        request_id: String,
+    },
+    RejectRefund {
+        request_id: String,
+    },
+    PlaceOrder {
+        agent_id: String,
+        base_asset: String,
+        quote_asset: String,
+        side: OrderSide,
+        price: u128,
+        amount: u128,
+    },
+    CancelOrder {
+        order_id: String,
+    },
+    CreateAmmPool {
+        agent_id: String,
+        seed_asset: String,
+        paired_asset: String,
+        paired_amount: u128,
+    },
+    AddLiquidity {
+        agent_id: String,
+        pool_id: String,
+        max_amount_a: u128,
+        max_amount_b: u128,
+    },
+    RemoveLiquidity {
+        agent_id: String,
+        pool_id: String,
+        shares: u128,
+    },
+    Swap {
+        agent_id: String,
+        pool_id: String,
+        asset_in: String,
+        amount_in: u128,
+        min_amount_out: u128,
+    },
+    SubmitFeedValue {
+        agent_id: String,
+        feed_id: String,
+        value: u128,
+    },
+    /// Returns the latest aggregated value of a feed, for other applications to call.
+    LatestFeedValue {
+        feed_id: String,
+    },
+    FundTreasury {
+        agent_id: String,
+        asset: String,
+        amount: u128,
+    },
+    CreateProposal {
+        agent_id: String,
+        description: String,
+        action: ProposalAction,
+    },
+    Vote {
+        agent_id: String,
+        proposal_id: String,
+        support: bool,
+    },
+    /// Delegates the agent's votes, or takes them back with `None`.
+    DelegateVotes {
+        agent_id: String,
+        delegate_agent: Option<String>,
+    },
+    ExecuteProposal {
+        proposal_id: String,
+    },
+    PublishListing {
+        agent_id: String,
+        service_type: String,
+        asset: String,
+        price: u128,
+        capacity: u32,
+        expires_at: Option<u64>,
+    },
+    PauseListing {
+        agent_id: String,
+        service_type: String,
+    },
+    ResumeListing {
+        agent_id: String,
+        service_type: String,
+    },
+    DeleteListing {
+        agent_id: String,
+        service_type: String,
+    },
+    /// Replaces the agent's strategy of the same kind, or adds it. `allocation_bps` and
+    /// `enabled` keep their current values when omitted.
     UpdateStrategy {
         agent_id: String,
         new_strategy: AgentStrategy,
+        allocation_bps: Option<u16>,
+        enabled: Option<bool>,
+    },
+    SetStrategyEnabled {
+        agent_id: String,
+        strategy_type: String,
+        enabled: bool,
+    },
+    /// Restores the strategies the agent had at `to_version` of its strategy history.
+    RollbackStrategy {
+        agent_id: String,
+        to_version: u64,
     },
     DeactivateAgent {
         agent_id: String,
@@ -48,6 +349,7 @@ pub enum Message {
         requester_chain: ChainId,
         provider_agent: String,
         service_type: String,
+        asset: String,
         payment: u128,
     },
     ServiceResponse {
@@ -58,10 +360,20 @@ pub enum Message {
     TokenTransfer {
         from_agent: String,
         to_agent: String,
+        asset: String,
         amount: u128,
     },
 }
 
+/// What an operation returns to its caller.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OperationResponse {
+    /// Outcome of an operation that changes state, for display.
+    Message(String),
+    /// Reply to `LatestFeedValue`, for other applications to read.
+    FeedValue(FeedValue),
+}
+
 pub struct AgentChainContract {
     state: AgentChainState<Self>,
     runtime: ContractRuntime<Self>,
@@ -71,7 +383,7 @@ pub struct AgentChainContract {
 impl Contract for AgentChainContract {
     type Message = Message;
     type Parameters = ();
-    type InstantiationArgument = ();
+    type InstantiationArgument = AgentChainConfig;
 
     async fn load(runtime: ContractRuntime<Self>) -> Self {
         let state = AgentChainState::load(runtime.root_view_storage_context())
@@ -80,17 +392,18 @@ impl Contract for AgentChainContract {
         AgentChainContract { state, runtime }
     }
 
-    async fn instantiate(&mut self, _argument: Self::InstantiationArgument) {
+    async fn instantiate(&mut self, argument: Self::InstantiationArgument) {
         self.runtime.application_parameters();
+        self.state.config.set(argument);
     }
 
     async fn execute_operation(&mut self, operation: Self::Operation) -> Self::Response {
-        match operation {
+        let now = self.get_current_timestamp();
+        let message = match operation {
             Operation::CreateAgent {
                 name,
                 description,
-                strategy,
-                initial_balance,
+                strategies,
             } => {
                 let owner = self.runtime.authenticated_signer()
                     .expect("Operation must be signed");
@@ -102,8 +415,8 @@ impl Contract for AgentChainContract {
                         owner.to_string(),
                         name,
                         description,
-                        strategy,
-                        initial_balance,
+                        strategies,
+                        now,
                     )
                     .await
                     .expect("Failed to create agent");
@@ -111,153 +424,1303 @@ impl Contract for AgentChainContract {
                 format!("Agent created: {}", agent_id)
             }
 
-            Operation::TransferTokens { to_agent, amount } => {
+            Operation::TransferTokens { from_agent, to_agent, asset, amount } => {
                 let owner = self.runtime.authenticated_signer()
                     .expect("Operation must be signed");
-                let from_agent = format!("agent_{}", owner);
+                self.state.get_owned_agent(&from_agent, &owner.to_string()).await
+                    .expect("Only the agent owner can transfer its tokens");
 
                 self.state
                     .transfer_tokens(
                         &from_agent,
                         &to_agent,
+                        &asset,
                         amount,
                         TransactionType::Transfer,
+                        now,
                     )
                     .await
                     .expect("Failed to transfer tokens");
 
-                format!("Transferred {} tokens to {}", amount, to_agent)
+                format!("Transferred {} {} to {}", amount, asset, to_agent)
             }
 
-            Operation::RequestService {
-                provider_agent,
-                service_type,
-                parameters,
-                payment,
-            } => {
+            Operation::Deposit { agent_id, asset, amount } => {
                 let owner = self.runtime.authenticated_signer()
                     .expect("Operation must be signed");
-                let requester_agent = format!("agent_{}", owner);
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can deposit to it");
 
-                let request_id = self.state
-                    .create_service_request(
-                        requester_agent.clone(),
-                        provider_agent.clone(),
-                        service_type.clone(),
-                        parameters.clone(),
-                        payment,
-                    )
+                self.pull_in(&asset, amount, owner);
+                let transaction_id = self.state
+                    .deposit(&agent_id, &asset, amount, now)
                     .await
-                    .expect("Failed to create service request");
+                    .expect("Failed to deposit");
 
-                let message = Message::ServiceRequest {
-                    request_id: request_id.clone(),
-                    requester_chain: self.runtime.chain_id(),
-                    provider_agent: provider_agent.clone(),
-                    service_type,
-                    payment,
+                format!("Deposited {} {} to {}: {}", amount, asset, agent_id, transaction_id)
+            }
+
+            Operation::Withdraw { agent_id, asset, amount } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can withdraw its earnings");
+
+                let transaction_id = self.state
+                    .withdraw(&agent_id, &asset, amount, now)
+                    .await
+                    .expect("Failed to withdraw");
+
+                let destination = Account {
+                    chain_id: self.runtime.chain_id(),
+                    owner,
                 };
+                self.pay_out(&asset, amount, destination);
+
+                format!("Withdrew {} {} from {}: {}", amount, asset, agent_id, transaction_id)
+            }
 
-                self.runtime
-                    .prepare_message(message)
-                    .send_to(self.runtime.chain_id());
+            Operation::SetRevenueSplit { agent_id, split } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can set its revenue split");
 
-                format!("Service request created: {}", request_id)
+                self.state
+                    .set_revenue_split(&agent_id, split)
+                    .await
+                    .expect("Failed to set revenue split");
+
+                format!("Revenue split updated for agent: {}", agent_id)
             }
 
-            Operation::CompleteService { request_id, success } => {
+            Operation::SetSpendingPolicy { agent_id, policy } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can set its spending policy");
+
                 self.state
-                    .complete_service(&request_id, success)
+                    .set_spending_policy(&agent_id, policy)
                     .await
-                    .expect("Failed to complete service");
+                    .expect("Failed to set spending policy");
 
-                format!("Service {} marked as {}", request_id, if success { "completed" } else { "failed" })
+                format!("Spending policy updated for agent: {}", agent_id)
             }
 
-            Operation::UpdateStrategy { agent_id, new_strategy } => {
-                let mut agent = self.state.get_agent(&agent_id).await
-                    .expect("Agent not found");
-                
-                agent.strategy = new_strategy;
-                agent.last_active = self.runtime.system_time().micros() / 1_000_000;
-                
-                self.state.agents.insert(&agent_id, agent)
-                    .expect("Failed to update agent");
+            Operation::Approve {
+                owner_agent,
+                spender_agent,
+                asset,
+                amount,
+                expires_at,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&owner_agent, &owner.to_string()).await
+                    .expect("Only the agent owner can approve spending");
+
+                self.state
+                    .approve(&owner_agent, &spender_agent, &asset, amount, expires_at, now)
+                    .await
+                    .expect("Failed to approve allowance");
 
-                format!("Strategy updated for agent: {}", agent_id)
+                format!("Approved {} to spend {} {} from {}", spender_agent, amount, asset, owner_agent)
             }
 
-            Operation::DeactivateAgent { agent_id } => {
-                let mut agent = self.state.get_agent(&agent_id).await
-                    .expect("Agent not found");
-                
-                agent.is_active = false;
-                self.state.agents.insert(&agent_id, agent)
-                    .expect("Failed to deactivate agent");
+            Operation::TransferFrom {
+                spender_agent,
+                from_agent,
+                to_agent,
+                asset,
+                amount,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&spender_agent, &owner.to_string()).await
+                    .expect("Only the spender's owner can use its allowance");
+
+                let transaction_id = self.state
+                    .transfer_from(&spender_agent, &from_agent, &to_agent, &asset, amount, now)
+                    .await
+                    .expect("Failed to transfer from allowance");
 
-                format!("Agent deactivated: {}", agent_id)
+                format!("Transferred {} {} from {} to {}: {}", amount, asset, from_agent, to_agent, transaction_id)
             }
 
-            Operation::AcceptService { request_id } => {
-                let mut request = self.state.service_requests.get(&request_id).await
-                    .expect("Failed to get request")
-                    .expect("Request not found");
-                
-                request.status = ServiceStatus::Accepted;
-                self.state.service_requests.insert(&request_id, request)
-                    .expect("Failed to update request");
+            Operation::RevokeAllowance {
+                owner_agent,
+                spender_agent,
+                asset,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&owner_agent, &owner.to_string()).await
+                    .expect("Only the agent owner can revoke allowances");
 
-                format!("Service request accepted: {}", request_id)
+                self.state
+                    .revoke_allowance(&owner_agent, &spender_agent, &asset)
+                    .await
+                    .expect("Failed to revoke allowance");
+
+                format!("Revoked {} allowance of {} over {}", asset, spender_agent, owner_agent)
             }
-        }
-    }
 
-    async fn execute_message(&mut self, message: Self::Message) {
-        match message {
-            Message::ServiceRequest {
-                request_id,
-                requester_chain,
+            Operation::RequestService {
+                requester_agent,
                 provider_agent,
                 service_type,
+                parameters,
+                asset,
                 payment,
+                milestones,
+                insured,
+                on_credit,
             } => {
-                let mut agent = self.state.get_agent(&provider_agent).await
-                    .expect("Provider agent not found");
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&requester_agent, &owner.to_string()).await
+                    .expect("Only the requester's owner can request services for it");
 
-                agent.last_active = self.runtime.system_time().micros() / 1_000_000;
-                self.state.agents.insert(&provider_agent, agent)
-                    .expect("Failed to update agent");
+                let terms = ServiceTerms {
+                    service_type,
+                    parameters,
+                    asset,
+                    payment,
+                    milestones,
+                    insured,
+                };
+                let provider = self.state.get_agent(&provider_agent).await
+                    .expect("Provider not found");
+                let price = self.consult_custom_strategy(&requester_agent, &provider, &terms)
+                    .expect("Provider's strategy declined the request");
+                if let Some(price) = price {
+                    assert!(payment >= price, "Payment is below the provider's price of {}", price);
+                }
+                let request_id = if on_credit {
+                    self.state
+                        .create_credit_service_request(requester_agent, provider_agent, terms, now)
+                        .await
+                } else {
+                    self.state
+                        .create_service_request(requester_agent, provider_agent, terms, now)
+                        .await
+                }
+                .expect("Failed to create service request");
+
+                self.notify_provider(&request_id).await;
+
+                format!("Service request created: {}", request_id)
             }
 
-            Message::ServiceResponse {
-                request_id,
-                success,
-                data,
+            Operation::RequestBestProvider {
+                requester_agent,
+                service_type,
+                parameters,
+                asset,
+                max_price,
+                weights,
+                insured,
             } => {
-                self.state.complete_service(&request_id, success).await
-                    .expect("Failed to process service response");
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&requester_agent, &owner.to_string()).await
+                    .expect("Only the requester's owner can request services for it");
+
+                let candidates = self.state
+                    .rank_providers(&requester_agent, &service_type, &asset, max_price, &weights, now)
+                    .await
+                    .expect("Failed to rank providers");
+
+                // Providers whose strategy declines or asks more than `max_price` are
+                // passed over for the next best one.
+                let mut chosen = None;
+                for listing in candidates {
+                    let mut terms = ServiceTerms {
+                        service_type: service_type.clone(),
+                        parameters: parameters.clone(),
+                        asset: asset.clone(),
+                        payment: listing.price,
+                        milestones: Vec::new(),
+                        insured,
+                    };
+                    let provider = self.state.get_agent(&listing.agent_id).await
+                        .expect("Provider not found");
+                    match self.consult_custom_strategy(&requester_agent, &provider, &terms) {
+                        Ok(Some(price)) if price <= max_price => terms.payment = price,
+                        Ok(None) => {}
+                        Ok(Some(_)) | Err(_) => continue,
+                    }
+                    chosen = Some((listing.agent_id, terms));
+                    break;
+                }
+                let (provider_agent, terms) = chosen.expect("No provider accepted the request");
+
+                let request_id = self.state
+                    .create_service_request(requester_agent, provider_agent.clone(), terms, now)
+                    .await
+                    .expect("Failed to create service request");
+
+                self.notify_provider(&request_id).await;
+
+                format!("Service request created with {}: {}", provider_agent, request_id)
             }
 
-            Message::TokenTransfer {
-                from_agent,
-                to_agent,
-                amount,
+            Operation::PostJob {
+                requester_agent,
+                service_type,
+                parameters,
+                asset,
+                payment,
+                min_reputation,
+                required_stake,
             } => {
-                self.state
-                    .transfer_tokens(&from_agent, &to_agent, amount, TransactionType::Transfer)
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&requester_agent, &owner.to_string()).await
+                    .expect("Only the requester's owner can post a job");
+
+                let job_id = self.state
+                    .post_job(
+                        &requester_agent,
+                        service_type,
+                        parameters,
+                        asset,
+                        payment,
+                        min_reputation,
+                        required_stake,
+                        now,
+                    )
                     .await
-                    .expect("Failed to execute cross-chain transfer");
+                    .expect("Failed to post job");
+
+                format!("Job posted: {}", job_id)
             }
-        }
-    }
 
-    async fn store(mut self) {
-        self.state.save().await.expect("Failed to save state");
-    }
-}
+            Operation::ClaimJob { job_id, provider_agent } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&provider_agent, &owner.to_string()).await
+                    .expect("Only the provider's owner can claim a job on its behalf");
+
+                let request_id = self.state
+                    .claim_job(&job_id, &provider_agent, now)
+                    .await
+                    .expect("Failed to claim job");
+
+                self.notify_provider(&request_id).await;
+
+                format!("Job {} claimed by {}: {}", job_id, provider_agent, request_id)
+            }
+
+            Operation::CancelJob { job_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let job = self.state.get_job(&job_id).await
+                    .expect("Job not found");
+                self.state.get_owned_agent(&job.requester_agent, &owner.to_string()).await
+                    .expect("Only the requester can cancel a job");
+
+                self.state
+                    .cancel_job(&job_id)
+                    .await
+                    .expect("Failed to cancel job");
+
+                format!("Job cancelled: {}", job_id)
+            }
+
+            Operation::PostRfq {
+                requester_agent,
+                service_type,
+                parameters,
+                asset,
+                max_budget,
+                bidding_deadline,
+                insured,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&requester_agent, &owner.to_string()).await
+                    .expect("Only the requester's owner can post an RFQ");
+
+                let rfq_id = self.state
+                    .post_rfq(
+                        &requester_agent,
+                        service_type,
+                        parameters,
+                        asset,
+                        max_budget,
+                        bidding_deadline,
+                        insured,
+                        now,
+                    )
+                    .await
+                    .expect("Failed to post RFQ");
+
+                format!("RFQ posted: {}", rfq_id)
+            }
+
+            Operation::SubmitBid { rfq_id, provider_agent, price } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&provider_agent, &owner.to_string()).await
+                    .expect("Only the provider's owner can bid on its behalf");
+
+                self.state
+                    .submit_bid(&rfq_id, &provider_agent, price, now)
+                    .await
+                    .expect("Failed to submit bid");
+
+                format!("Bid of {} submitted on {}", price, rfq_id)
+            }
+
+            Operation::AwardRfq { rfq_id, provider_agent } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let rfq = self.state.get_rfq(&rfq_id).await
+                    .expect("RFQ not found");
+                self.state.get_owned_agent(&rfq.requester_agent, &owner.to_string()).await
+                    .expect("Only the requester can award an RFQ");
+
+                let request_id = self.state
+                    .award_rfq(&rfq_id, &provider_agent, now)
+                    .await
+                    .expect("Failed to award RFQ");
+
+                self.notify_provider(&request_id).await;
+
+                format!("RFQ {} awarded to {}: {}", rfq_id, provider_agent, request_id)
+            }
+
+            Operation::AutoAwardRfq { rfq_id } => {
+                let (provider_agent, request_id) = self.state
+                    .auto_award_rfq(&rfq_id, now)
+                    .await
+                    .expect("Failed to auto-award RFQ");
+
+                self.notify_provider(&request_id).await;
+
+                format!("RFQ {} awarded to {}: {}", rfq_id, provider_agent, request_id)
+            }
+
+            Operation::CancelRfq { rfq_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let rfq = self.state.get_rfq(&rfq_id).await
+                    .expect("RFQ not found");
+                self.state.get_owned_agent(&rfq.requester_agent, &owner.to_string()).await
+                    .expect("Only the requester can cancel an RFQ");
+
+                self.state
+                    .cancel_rfq(&rfq_id)
+                    .await
+                    .expect("Failed to cancel RFQ");
+
+                format!("RFQ cancelled: {}", rfq_id)
+            }
+
+            Operation::CompleteService { request_id, success } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                if success {
+                    self.state.get_owned_agent(&request.requester_agent, &owner.to_string()).await
+                        .expect("Only the requester can mark a request as completed");
+                } else {
+                    self.state.get_owned_agent(&request.provider_agent, &owner.to_string()).await
+                        .expect("Only the provider can mark a request as failed");
+                }
+
+                self.state
+                    .complete_service(&request_id, success, now)
+                    .await
+                    .expect("Failed to complete service");
+
+                format!("Service {} marked as {}", request_id, if success { "completed" } else { "failed" })
+            }
+
+            Operation::OpenDispute { request_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                let requester = self.state.get_agent(&request.requester_agent).await
+                    .expect("Requester not found");
+                let provider = self.state.get_agent(&request.provider_agent).await
+                    .expect("Provider not found");
+                assert!(
+                    requester.owner == owner.to_string() || provider.owner == owner.to_string(),
+                    "Only the requester or the provider can dispute a request"
+                );
+
+                self.state
+                    .open_dispute(&request_id)
+                    .await
+                    .expect("Failed to open dispute");
+
+                format!("Dispute opened for {}", request_id)
+            }
+
+            Operation::ResolveDispute { request_id, requester_wins } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                assert_eq!(
+                    self.state.config.get().dispute_arbiter.as_deref(),
+                    Some(owner.to_string().as_str()),
+                    "Only the dispute arbiter can resolve disputes"
+                );
+
+                self.state
+                    .resolve_dispute(&request_id, requester_wins, now)
+                    .await
+                    .expect("Failed to resolve dispute");
+
+                format!(
+                    "Dispute for {} resolved in favor of the {}",
+                    request_id,
+                    if requester_wins { "requester" } else { "provider" }
+                )
+            }
+
+            Operation::SubmitMilestone { request_id, milestone_index } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.provider_agent, &owner.to_string()).await
+                    .expect("Only the provider can submit milestones");
+
+                self.state
+                    .submit_milestone(&request_id, milestone_index as usize, now)
+                    .await
+                    .expect("Failed to submit milestone");
+
+                format!("Milestone {} submitted for {}", milestone_index, request_id)
+            }
+
+            Operation::AcceptMilestone { request_id, milestone_index } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.requester_agent, &owner.to_string()).await
+                    .expect("Only the requester can accept milestones");
+
+                self.state
+                    .accept_milestone(&request_id, milestone_index as usize, now)
+                    .await
+                    .expect("Failed to accept milestone");
+
+                format!("Milestone {} accepted for {}", milestone_index, request_id)
+            }
+
+            Operation::RejectMilestone { request_id, milestone_index } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.requester_agent, &owner.to_string()).await
+                    .expect("Only the requester can reject milestones");
+
+                self.state
+                    .reject_milestone(&request_id, milestone_index as usize)
+                    .await
+                    .expect("Failed to reject milestone");
+
+                format!("Milestone {} rejected for {}", milestone_index, request_id)
+            }
+
+            Operation::DepositToPool { agent_id, asset, amount } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can manage its lending position");
+
+                let shares = self.state
+                    .deposit_to_pool(&agent_id, &asset, amount, now)
+                    .await
+                    .expect("Failed to deposit to lending pool");
+
+                format!("Deposited {} {} for {} pool shares", amount, asset, shares)
+            }
+
+            Operation::WithdrawFromPool { agent_id, asset, shares } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can manage its lending position");
+
+                let amount = self.state
+                    .withdraw_from_pool(&agent_id, &asset, shares, now)
+                    .await
+                    .expect("Failed to withdraw from lending pool");
+
+                format!("Withdrew {} {} for {} pool shares", amount, asset, shares)
+            }
+
+            Operation::AddCollateral {
+                agent_id,
+                asset,
+                collateral_asset,
+                amount,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can manage its lending position");
+
+                self.state
+                    .add_collateral(&agent_id, &asset, &collateral_asset, amount, now)
+                    .await
+                    .expect("Failed to add collateral");
+
+                format!(
+                    "Added {} {} of collateral against {} for {}",
+                    amount, collateral_asset, asset, agent_id
+                )
+            }
+
+            Operation::RemoveCollateral { agent_id, asset, amount } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can manage its lending position");
+
+                self.state
+                    .remove_collateral(&agent_id, &asset, amount, now)
+                    .await
+                    .expect("Failed to remove collateral");
+
+                format!("Removed {} of collateral against {} for {}", amount, asset, agent_id)
+            }
+
+            Operation::Borrow { agent_id, asset, amount } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can manage its lending position");
+
+                self.state
+                    .borrow_from_pool(&agent_id, &asset, amount, now)
+                    .await
+                    .expect("Failed to borrow from lending pool");
+
+                format!("Borrowed {} {} for {}", amount, asset, agent_id)
+            }
+
+            Operation::Repay { agent_id, asset, amount } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can manage its lending position");
+
+                let repaid = self.state
+                    .repay_to_pool(&agent_id, &asset, amount, now)
+                    .await
+                    .expect("Failed to repay lending pool");
+
+                format!("Repaid {} {} for {}", repaid, asset, agent_id)
+            }
+
+            Operation::Liquidate {
+                liquidator_agent,
+                borrower_agent,
+                asset,
+                amount,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&liquidator_agent, &owner.to_string()).await
+                    .expect("Only the liquidator's owner can liquidate on its behalf");
+
+                let seized = self.state
+                    .liquidate(&liquidator_agent, &borrower_agent, &asset, amount, now)
+                    .await
+                    .expect("Failed to liquidate position");
+
+                format!("Liquidated {}'s {} loan: seized {} of collateral", borrower_agent, asset, seized)
+            }
+
+            Operation::SetCreditLimit {
+                creditor_agent,
+                debtor_agent,
+                asset,
+                limit,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&creditor_agent, &owner.to_string()).await
+                    .expect("Only the creditor can set a credit limit");
+
+                self.state
+                    .set_credit_limit(&creditor_agent, &debtor_agent, &asset, limit, now)
+                    .await
+                    .expect("Failed to set credit limit");
+
+                format!("Credit limit of {} {} offered to {}", limit, asset, debtor_agent)
+            }
+
+            Operation::AcceptCreditLimit {
+                creditor_agent,
+                debtor_agent,
+                asset,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&debtor_agent, &owner.to_string()).await
+                    .expect("Only the debtor can accept a credit limit");
+
+                let limit = self.state
+                    .accept_credit_limit(&creditor_agent, &debtor_agent, &asset, now)
+                    .await
+                    .expect("Failed to accept credit limit");
+
+                format!("Credit limit of {} {} accepted from {}", limit, asset, creditor_agent)
+            }
+
+            Operation::IssueInvoice {
+                request_id,
+                description,
+                term_seconds,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.provider_agent, &owner.to_string()).await
+                    .expect("Only the provider can invoice a request");
+
+                let invoice_id = self.state
+                    .issue_invoice(&request_id, description, term_seconds, now)
+                    .await
+                    .expect("Failed to issue invoice");
+
+                format!("Invoice issued: {}", invoice_id)
+            }
+
+            Operation::SettleInvoice { invoice_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let invoice = self.state.get_invoice(&invoice_id).await
+                    .expect("Invoice not found");
+                self.state.get_owned_agent(&invoice.debtor_agent, &owner.to_string()).await
+                    .expect("Only the debtor can settle an invoice");
+
+                let transaction_id = self.state
+                    .settle_invoice(&invoice_id, now)
+                    .await
+                    .expect("Failed to settle invoice");
+
+                format!("Invoice {} settled: {}", invoice_id, transaction_id)
+            }
+
+            Operation::MarkInvoiceOverdue { invoice_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let invoice = self.state.get_invoice(&invoice_id).await
+                    .expect("Invoice not found");
+                self.state.get_owned_agent(&invoice.creditor_agent, &owner.to_string()).await
+                    .expect("Only the creditor can mark an invoice overdue");
+
+                self.state
+                    .mark_invoice_overdue(&invoice_id, now)
+                    .await
+                    .expect("Failed to mark invoice overdue");
+
+                format!("Invoice {} marked as overdue", invoice_id)
+            }
+
+            Operation::RefundPayment { request_id, amount } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.provider_agent, &owner.to_string()).await
+                    .expect("Only the provider can issue refunds");
+
+                let transaction_ids = self.state
+                    .refund_payment(&request_id, amount, now)
+                    .await
+                    .expect("Failed to refund payment");
+
+                format!("Refunded {} for {}: {}", amount, request_id, transaction_ids.join(", "))
+            }
+
+            Operation::RequestRefund { request_id, amount, reason } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.requester_agent, &owner.to_string()).await
+                    .expect("Only the requester can ask for a refund");
+
+                self.state
+                    .request_refund(&request_id, amount, reason, now)
+                    .await
+                    .expect("Failed to request refund");
+
+                format!("Refund of {} requested for {}", amount, request_id)
+            }
+
+            Operation::ApproveRefund { request_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.provider_agent, &owner.to_string()).await
+                    .expect("Only the provider can approve refunds");
+
+                let transaction_ids = self.state
+                    .approve_refund(&request_id, now)
+                    .await
+                    .expect("Failed to approve refund");
+
+                format!("Refund approved for {}: {}", request_id, transaction_ids.join(", "))
+            }
+
+            Operation::RejectRefund { request_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.provider_agent, &owner.to_string()).await
+                    .expect("Only the provider can reject refunds");
+
+                self.state
+                    .reject_refund(&request_id)
+                    .await
+                    .expect("Failed to reject refund");
+
+                format!("Refund rejected for {}", request_id)
+            }
+
+            Operation::PlaceOrder {
+                agent_id,
+                base_asset,
+                quote_asset,
+                side,
+                price,
+                amount,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can place its orders");
+
+                let (order_id, fills) = self.state
+                    .place_order(&agent_id, base_asset, quote_asset, side, price, amount, now)
+                    .await
+                    .expect("Failed to place order");
+                let filled: u128 = fills.iter().map(|fill| fill.amount).sum();
+
+                format!("Order placed: {} ({} fills, {} filled)", order_id, fills.len(), filled)
+            }
+
+            Operation::CancelOrder { order_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let order = self.state.get_order(&order_id).await
+                    .expect("Order not found");
+                self.state.get_owned_agent(&order.agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can cancel its orders");
+
+                self.state
+                    .cancel_order(&order_id)
+                    .await
+                    .expect("Failed to cancel order");
+
+                format!("Order cancelled: {}", order_id)
+            }
+
+            Operation::CreateAmmPool {
+                agent_id,
+                seed_asset,
+                paired_asset,
+                paired_amount,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can create pools for it");
+
+                let pool_id = self.state
+                    .create_amm_pool(&agent_id, seed_asset, paired_asset, paired_amount, now)
+                    .await
+                    .expect("Failed to create pool");
+
+                format!("Pool created: {}", pool_id)
+            }
+
+            Operation::AddLiquidity {
+                agent_id,
+                pool_id,
+                max_amount_a,
+                max_amount_b,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can add its liquidity");
+
+                let shares = self.state
+                    .add_liquidity(&agent_id, &pool_id, max_amount_a, max_amount_b, now)
+                    .await
+                    .expect("Failed to add liquidity");
+
+                format!("Added liquidity to {}: {} shares", pool_id, shares)
+            }
+
+            Operation::RemoveLiquidity { agent_id, pool_id, shares } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can remove its liquidity");
+
+                let (amount_a, amount_b) = self.state
+                    .remove_liquidity(&agent_id, &pool_id, shares)
+                    .await
+                    .expect("Failed to remove liquidity");
+
+                format!("Removed liquidity from {}: {} and {}", pool_id, amount_a, amount_b)
+            }
+
+            Operation::Swap {
+                agent_id,
+                pool_id,
+                asset_in,
+                amount_in,
+                min_amount_out,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can swap its balances");
+
+                let amount_out = self.state
+                    .swap(&agent_id, &pool_id, &asset_in, amount_in, min_amount_out, now)
+                    .await
+                    .expect("Failed to swap");
+
+                format!("Swapped {} {} for {} in {}", amount_in, asset_in, amount_out, pool_id)
+            }
+
+            Operation::SubmitFeedValue { agent_id, feed_id, value } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can submit its feed values");
+
+                let aggregated = self.state
+                    .submit_feed_value(&agent_id, &feed_id, value, now)
+                    .await
+                    .expect("Failed to submit feed value");
+
+                match aggregated {
+                    Some(feed_value) => format!(
+                        "Feed {} updated to {} from {} sources",
+                        feed_id, feed_value.value, feed_value.sources
+                    ),
+                    None => format!("Value recorded for feed {}", feed_id),
+                }
+            }
+
+            Operation::LatestFeedValue { feed_id } => {
+                let feed_value = self.state
+                    .latest_feed_value(&feed_id)
+                    .await
+                    .expect("Failed to read feed");
+
+                return OperationResponse::FeedValue(feed_value);
+            }
+
+            Operation::FundTreasury { agent_id, asset, amount } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can fund the treasury from it");
+
+                let transaction_id = self.state
+                    .fund_treasury(&agent_id, &asset, amount, now)
+                    .await
+                    .expect("Failed to fund treasury");
+
+                format!("Treasury funded: {}", transaction_id)
+            }
+
+            Operation::CreateProposal { agent_id, description, action } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can propose for it");
+
+                let proposal_id = self.state
+                    .create_proposal(&agent_id, description, action, now)
+                    .await
+                    .expect("Failed to create proposal");
+
+                format!("Proposal created: {}", proposal_id)
+            }
+
+            Operation::Vote { agent_id, proposal_id, support } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can vote for it");
+
+                let weight = self.state
+                    .cast_vote(&proposal_id, &agent_id, support, now)
+                    .await
+                    .expect("Failed to vote");
+
+                format!("Voted on {} with weight {}", proposal_id, weight)
+            }
+
+            Operation::DelegateVotes { agent_id, delegate_agent } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can delegate its votes");
+
+                self.state
+                    .delegate_votes(&agent_id, delegate_agent.clone())
+                    .await
+                    .expect("Failed to delegate votes");
+
+                match delegate_agent {
+                    Some(delegate_agent) => format!("Votes of {} delegated to {}", agent_id, delegate_agent),
+                    None => format!("Delegation cleared for {}", agent_id),
+                }
+            }
+
+            Operation::ExecuteProposal { proposal_id } => {
+                let executed = self.state
+                    .execute_proposal(&proposal_id, now)
+                    .await
+                    .expect("Failed to execute proposal");
+
+                if executed {
+                    format!("Proposal executed: {}", proposal_id)
+                } else {
+                    format!("Proposal rejected: {}", proposal_id)
+                }
+            }
+
+            Operation::PublishListing {
+                agent_id,
+                service_type,
+                asset,
+                price,
+                capacity,
+                expires_at,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let agent = self.state.get_agent(&agent_id).await
+                    .expect("Agent not found");
+                assert!(agent.is_active, "Inactive agents cannot publish listings");
+
+                let listing = MarketListing {
+                    agent_id: agent_id.clone(),
+                    service_type: service_type.clone(),
+                    asset,
+                    price,
+                    capacity,
+                    average_completion_time: 0,
+                    success_rate: 0.0,
+                    status: ListingStatus::Active,
+                    expires_at,
+                };
+                self.state
+                    .update_market_listing(&owner.to_string(), listing)
+                    .await
+                    .expect("Failed to publish listing");
+
+                format!("Listing published: {} {}", agent_id, service_type)
+            }
+
+            Operation::PauseListing { agent_id, service_type } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can pause its listings");
+
+                self.state
+                    .set_listing_status(&agent_id, &service_type, ListingStatus::Paused)
+                    .await
+                    .expect("Failed to pause listing");
+
+                format!("Listing paused: {} {}", agent_id, service_type)
+            }
+
+            Operation::ResumeListing { agent_id, service_type } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can resume its listings");
+
+                self.state
+                    .set_listing_status(&agent_id, &service_type, ListingStatus::Active)
+                    .await
+                    .expect("Failed to resume listing");
+
+                format!("Listing resumed: {} {}", agent_id, service_type)
+            }
+
+            Operation::DeleteListing { agent_id, service_type } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can delete its listings");
+
+                self.state
+                    .delete_market_listing(&agent_id, &service_type)
+                    .await
+                    .expect("Failed to delete listing");
+
+                format!("Listing deleted: {} {}", agent_id, service_type)
+            }
+
+            Operation::UpdateStrategy {
+                agent_id,
+                new_strategy,
+                allocation_bps,
+                enabled,
+            } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can update its strategies");
+
+                let strategy_type = new_strategy.kind();
+                self.state
+                    .update_strategy(&agent_id, &owner.to_string(), new_strategy, allocation_bps, enabled, now)
+                    .await
+                    .expect("Failed to update strategy");
+
+                format!("{} strategy updated for agent: {}", strategy_type, agent_id)
+            }
+
+            Operation::SetStrategyEnabled { agent_id, strategy_type, enabled } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can update its strategies");
+
+                self.state
+                    .set_strategy_enabled(&agent_id, &owner.to_string(), &strategy_type, enabled, now)
+                    .await
+                    .expect("Failed to update strategy");
+
+                let action = if enabled { "enabled" } else { "disabled" };
+                format!("{} strategy {} for agent: {}", strategy_type, action, agent_id)
+            }
+
+            Operation::RollbackStrategy { agent_id, to_version } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can roll back its strategies");
+
+                self.state
+                    .rollback_strategy(&agent_id, &owner.to_string(), to_version, now)
+                    .await
+                    .expect("Failed to roll back strategy");
+
+                format!("Strategies of {} rolled back to version {}", agent_id, to_version)
+            }
+
+            Operation::DeactivateAgent { agent_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let mut agent = self.state.get_owned_agent(&agent_id, &owner.to_string()).await
+                    .expect("Only the agent owner can deactivate it");
+                
+                agent.is_active = false;
+                self.state.agents.insert(&agent_id, agent)
+                    .expect("Failed to deactivate agent");
+                let delisted = self.state.delist_agent(&agent_id).await
+                    .expect("Failed to delist agent");
+
+                format!("Agent deactivated: {} ({} listings removed)", agent_id, delisted)
+            }
+
+            Operation::AcceptService { request_id } => {
+                let owner = self.runtime.authenticated_signer()
+                    .expect("Operation must be signed");
+                let request = self.state.get_service_request(&request_id).await
+                    .expect("Request not found");
+                self.state.get_owned_agent(&request.provider_agent, &owner.to_string()).await
+                    .expect("Only the provider can accept a request");
+
+                self.state.accept_service(&request_id).await
+                    .expect("Failed to accept request");
+
+                format!("Service request accepted: {}", request_id)
+            }
+        };
+        OperationResponse::Message(message)
+    }
+
+    async fn execute_message(&mut self, message: Self::Message) {
+        let now = self.get_current_timestamp();
+        match message {
+            Message::ServiceRequest {
+                request_id,
+                requester_chain,
+                provider_agent,
+                service_type,
+                asset,
+                payment,
+            } => {
+                let mut agent = self.state.get_agent(&provider_agent).await
+                    .expect("Provider agent not found");
+
+                agent.last_active = now;
+                self.state.agents.insert(&provider_agent, agent)
+                    .expect("Failed to update agent");
+            }
+
+            Message::ServiceResponse {
+                request_id,
+                success,
+                data,
+            } => {
+                // Responses come from the provider's side, which may report a failure
+                // but cannot release the requester's escrow.
+                if !success {
+                    self.state.complete_service(&request_id, false, now).await
+                        .expect("Failed to process service response");
+                }
+            }
+
+            Message::TokenTransfer {
+                from_agent,
+                to_agent,
+                asset,
+                amount,
+            } => {
+                self.state
+                    .transfer_tokens(&from_agent, &to_agent, &asset, amount, TransactionType::Transfer, now)
+                    .await
+                    .expect("Failed to execute cross-chain transfer");
+            }
+        }
+    }
+
+    async fn store(mut self) {
+        self.state.save().await.expect("Failed to save state");
+    }
+}
 
 impl AgentChainContract {
     fn get_current_timestamp(&self) -> u64 {
         self.runtime.system_time().micros() / 1_000_000
     }
+
+    /// Tells the provider's chain about a newly created service request.
+    async fn notify_provider(&mut self, request_id: &str) {
+        let request = self.state.get_service_request(request_id).await
+            .expect("Request not found");
+
+        let message = Message::ServiceRequest {
+            request_id: request.id,
+            requester_chain: self.runtime.chain_id(),
+            provider_agent: request.provider_agent,
+            service_type: request.service_type,
+            asset: request.asset,
+            payment: request.payment,
+        };
+
+        self.runtime
+            .prepare_message(message)
+            .send_to(self.runtime.chain_id());
+    }
+
+    /// Asks the strategy application of a `Custom` provider whether it takes a request
+    /// on `terms`. Returns the price it asks, if any, or the reason it declined.
+    ///
+    /// The call is not authenticated, so the strategy cannot act with the signer's
+    /// authority.
+    fn consult_custom_strategy(
+        &mut self,
+        requester_agent: &str,
+        provider: &Agent,
+        terms: &ServiceTerms,
+    ) -> Result<Option<u128>, String> {
+        let Some(AgentStrategy::Custom { application_id, config_blob, schema_id }) = provider
+            .enabled_strategy("Custom")
+            .map(|slot| &slot.strategy)
+        else {
+            return Ok(None);
+        };
+
+        let request = StrategyRequest::EvaluateRequest {
+            agent_id: provider.id.clone(),
+            schema_id: schema_id.clone(),
+            config_blob: config_blob.clone(),
+            requester_agent: requester_agent.to_string(),
+            service_type: terms.service_type.clone(),
+            parameters: terms.parameters.clone(),
+            asset: terms.asset.clone(),
+            payment: terms.payment,
+        };
+        let strategy = application_id.with_abi::<CustomStrategyAbi>();
+        let decision = self.runtime.call_application(false, strategy, &request);
+        if !decision.accept {
+            return Err(decision.reason.unwrap_or_default());
+        }
+        Ok(decision.price)
+    }
+
+    /// Moves tokens from `owner` on this chain into the application's account, either
+    /// natively or through the fungible application configured for `asset`.
+    fn pull_in(&mut self, asset: &str, amount: u128, owner: AccountOwner) {
+        let destination = Account {
+            chain_id: self.runtime.chain_id(),
+            owner: AccountOwner::from(self.runtime.application_id().forget_abi()),
+        };
+        let amount = Amount::from_attos(amount);
+
+        if asset == NATIVE_ASSET {
+            self.runtime.transfer(owner, destination, amount);
+        } else {
+            let token = self.state.config.get()
+                .fungible_applications
+                .get(asset)
+                .copied()
+                .expect("No fungible application configured for asset")
+                .with_abi::<FungibleTokenAbi>();
+            let transfer = FungibleOperation::Transfer {
+                owner,
+                amount,
+                target_account: destination,
+            };
+            self.runtime.call_application(true, token, &transfer);
+        }
+    }
+
+    /// Sends tokens held by the application to `destination`, either natively or through
+    /// the fungible application configured for `asset`.
+    fn pay_out(&mut self, asset: &str, amount: u128, destination: Account) {
+        let source = AccountOwner::from(self.runtime.application_id().forget_abi());
+        let amount = Amount::from_attos(amount);
+
+        if asset == NATIVE_ASSET {
+            self.runtime.transfer(source, destination, amount);
+        } else {
+            let token = self.state.config.get()
+                .fungible_applications
+                .get(asset)
+                .copied()
+                .expect("No fungible application configured for asset")
+                .with_abi::<FungibleTokenAbi>();
+            let transfer = FungibleOperation::Transfer {
+                owner: source,
+                amount,
+                target_account: destination,
+            };
+            self.runtime.call_application(true, token, &transfer);
+        }
+    }
 }
@@ -0,0 +1,376 @@
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{
+    AgentChainError, AgentChainState, ServiceRequest, ServiceStatus, ServiceTerms, TransactionType,
+};
+
+/// Reputation lost by a debtor each time one of its invoices goes overdue.
+pub const OVERDUE_INVOICE_PENALTY: u64 = 10;
+
+/// Shortest time a debtor is given to settle an invoice.
+pub const MIN_INVOICE_TERM_SECONDS: u64 = 86_400;
+
+/// How much `debtor_agent` may owe `creditor_agent` in `asset` through unpaid invoices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreditLine {
+    pub creditor_agent: String,
+    pub debtor_agent: String,
+    pub asset: String,
+    /// Limit the debtor has accepted.
+    pub limit: u128,
+    /// Higher limit offered by the creditor, waiting for the debtor to accept it.
+    pub proposed_limit: Option<u128>,
+    pub outstanding: u128,
+    pub updated_at: u64,
+}
+
+impl CreditLine {
+    fn new(creditor_agent: &str, debtor_agent: &str, asset: &str) -> Self {
+        CreditLine {
+            creditor_agent: creditor_agent.to_string(),
+            debtor_agent: debtor_agent.to_string(),
+            asset: asset.to_string(),
+            limit: 0,
+            proposed_limit: None,
+            outstanding: 0,
+            updated_at: 0,
+        }
+    }
+
+    pub fn available(&self) -> u128 {
+        self.limit.saturating_sub(self.outstanding)
+    }
+
+    /// Lowers the limit right away, or offers a higher one for the debtor to accept.
+    /// Lowering the limit below the outstanding amount only blocks new invoices.
+    pub fn set_limit(&mut self, limit: u128, now: u64) {
+        if limit <= self.limit {
+            self.limit = limit;
+            self.proposed_limit = None;
+        } else {
+            self.proposed_limit = Some(limit);
+        }
+        self.updated_at = now;
+    }
+
+    pub fn accept(&mut self, now: u64) -> Result<(), AgentChainError> {
+        let limit = self.proposed_limit.take().ok_or_else(|| {
+            AgentChainError::InvoiceFailed("No credit limit has been offered".to_string())
+        })?;
+        self.limit = limit;
+        self.updated_at = now;
+        Ok(())
+    }
+
+    /// Adds `amount` to what the debtor owes, within the accepted limit.
+    pub fn draw(&mut self, amount: u128, now: u64) -> Result<(), AgentChainError> {
+        let requested = self.outstanding.saturating_add(amount);
+        if requested > self.limit {
+            return Err(AgentChainError::CreditLimitExceeded {
+                limit: self.limit,
+                requested,
+            });
+        }
+        self.outstanding = requested;
+        self.updated_at = now;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invoice {
+    pub id: String,
+    /// Completed credit-backed service request the invoice bills for.
+    pub request_id: String,
+    pub creditor_agent: String,
+    pub debtor_agent: String,
+    pub asset: String,
+    pub amount: u128,
+    pub description: String,
+    pub issued_at: u64,
+    pub due_at: u64,
+    pub status: InvoiceStatus,
+    pub settled_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum InvoiceStatus {
+    Open,
+    Overdue,
+    Settled,
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    pub fn credit_line_key(creditor_agent: &str, debtor_agent: &str, asset: &str) -> String {
+        format!("{}_{}_{}", creditor_agent, debtor_agent, asset)
+    }
+
+    /// Sets how much `debtor_agent` may owe `creditor_agent`. Raising the limit only takes
+    /// effect once the debtor accepts it.
+    pub async fn set_credit_limit(
+        &mut self,
+        creditor_agent: &str,
+        debtor_agent: &str,
+        asset: &str,
+        limit: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        self.get_agent(debtor_agent).await?;
+
+        let key = Self::credit_line_key(creditor_agent, debtor_agent, asset);
+        let mut line = self
+            .credit_lines
+            .get(&key)
+            .await?
+            .unwrap_or_else(|| CreditLine::new(creditor_agent, debtor_agent, asset));
+        line.set_limit(limit, now);
+        self.credit_lines.insert(&key, line)?;
+        Ok(())
+    }
+
+    /// Accepts the limit `creditor_agent` has offered to `debtor_agent`.
+    pub async fn accept_credit_limit(
+        &mut self,
+        creditor_agent: &str,
+        debtor_agent: &str,
+        asset: &str,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        let key = Self::credit_line_key(creditor_agent, debtor_agent, asset);
+        let mut line = self.credit_lines.get(&key).await?.ok_or_else(|| {
+            AgentChainError::InvoiceFailed("No credit limit has been offered".to_string())
+        })?;
+        line.accept(now)?;
+        let limit = line.limit;
+        self.credit_lines.insert(&key, line)?;
+        Ok(limit)
+    }
+
+    /// Creates a service request paid on the credit line `provider_agent` has extended to
+    /// `requester_agent` instead of from the requester's balance. The payment is drawn
+    /// on the line up front, so the provider knows it is covered, and is billed with
+    /// `issue_invoice` once the request completes. Credit-backed requests have no
+    /// milestones or insurance, since nothing is held in escrow.
+    pub async fn create_credit_service_request(
+        &mut self,
+        requester_agent: String,
+        provider_agent: String,
+        terms: ServiceTerms,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        if !terms.milestones.is_empty() || terms.insured {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Credit-backed requests cannot have milestones or insurance".to_string(),
+            ));
+        }
+        self.check_provider_available(&provider_agent, &terms.service_type, now)
+            .await?;
+        self.check_service_type_allowed(&requester_agent, &terms.service_type)
+            .await?;
+
+        let key = Self::credit_line_key(&provider_agent, &requester_agent, &terms.asset);
+        let mut line = self.credit_lines.get(&key).await?.ok_or(
+            AgentChainError::CreditLimitExceeded {
+                limit: 0,
+                requested: terms.payment,
+            },
+        )?;
+        line.draw(terms.payment, now)?;
+        self.credit_lines.insert(&key, line)?;
+
+        let request_id = self
+            .open_service_request(requester_agent, provider_agent, terms, now)
+            .await?;
+        let mut request = self.get_service_request(&request_id).await?;
+        request.escrow = 0;
+        request.on_credit = true;
+        self.service_requests.insert(&request_id, request)?;
+        Ok(request_id)
+    }
+
+    /// Gives back the credit a failed credit-backed request drew.
+    pub(crate) async fn release_credit(
+        &mut self,
+        request: &ServiceRequest,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let key = Self::credit_line_key(&request.provider_agent, &request.requester_agent, &request.asset);
+        if let Some(mut line) = self.credit_lines.get(&key).await? {
+            line.outstanding = line.outstanding.saturating_sub(request.payment);
+            line.updated_at = now;
+            self.credit_lines.insert(&key, line)?;
+        }
+        Ok(())
+    }
+
+    /// Bills the requester of a completed credit-backed request for its payment, to be
+    /// settled within `term_seconds`. The credit was drawn when the request was made.
+    /// Each request can be invoiced once.
+    pub async fn issue_invoice(
+        &mut self,
+        request_id: &str,
+        description: String,
+        term_seconds: u64,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        if term_seconds < MIN_INVOICE_TERM_SECONDS {
+            return Err(AgentChainError::InvoiceFailed(format!(
+                "Invoices must give at least {} seconds to pay",
+                MIN_INVOICE_TERM_SECONDS
+            )));
+        }
+        let request = self.get_service_request(request_id).await?;
+        if !request.on_credit {
+            return Err(AgentChainError::InvoiceFailed(
+                "Only credit-backed requests can be invoiced".to_string(),
+            ));
+        }
+        if request.status != ServiceStatus::Completed {
+            return Err(AgentChainError::InvoiceFailed(
+                "Only completed requests can be invoiced".to_string(),
+            ));
+        }
+        if self.request_invoices.contains_key(request_id).await? {
+            return Err(AgentChainError::InvoiceFailed(
+                "Request has already been invoiced".to_string(),
+            ));
+        }
+
+        let mut total_invoices = *self.total_invoices.get();
+        let invoice_id = format!("inv_{}_{}", now, total_invoices);
+        let invoice = Invoice {
+            id: invoice_id.clone(),
+            request_id: request_id.to_string(),
+            creditor_agent: request.provider_agent,
+            debtor_agent: request.requester_agent,
+            asset: request.asset,
+            amount: request.payment,
+            description,
+            issued_at: now,
+            due_at: now.saturating_add(term_seconds),
+            status: InvoiceStatus::Open,
+            settled_at: None,
+        };
+        self.invoices.insert(&invoice_id, invoice)?;
+        self.request_invoices.insert(request_id, invoice_id.clone())?;
+
+        total_invoices += 1;
+        self.total_invoices.set(total_invoices);
+
+        Ok(invoice_id)
+    }
+
+    pub async fn get_invoice(&self, invoice_id: &str) -> Result<Invoice, AgentChainError> {
+        self.invoices
+            .get(invoice_id)
+            .await?
+            .ok_or_else(|| AgentChainError::InvoiceFailed("Invoice not found".to_string()))
+    }
+
+    /// Pays an open or overdue invoice from the debtor's balance and frees up its credit.
+    pub async fn settle_invoice(&mut self, invoice_id: &str, now: u64) -> Result<String, AgentChainError> {
+        let mut invoice = self.get_invoice(invoice_id).await?;
+        if invoice.status == InvoiceStatus::Settled {
+            return Err(AgentChainError::InvoiceFailed(
+                "Invoice is already settled".to_string(),
+            ));
+        }
+
+        let transaction_id = self
+            .transfer_tokens(
+                &invoice.debtor_agent,
+                &invoice.creditor_agent,
+                &invoice.asset,
+                invoice.amount,
+                TransactionType::InvoiceSettlement,
+                now,
+            )
+            .await?;
+
+        let key = Self::credit_line_key(&invoice.creditor_agent, &invoice.debtor_agent, &invoice.asset);
+        if let Some(mut line) = self.credit_lines.get(&key).await? {
+            line.outstanding = line.outstanding.saturating_sub(invoice.amount);
+            line.updated_at = now;
+            self.credit_lines.insert(&key, line)?;
+        }
+
+        invoice.status = InvoiceStatus::Settled;
+        invoice.settled_at = Some(now);
+        self.invoices.insert(invoice_id, invoice)?;
+
+        Ok(transaction_id)
+    }
+
+    /// Flags an open invoice past its due date and lowers the debtor's reputation.
+    pub async fn mark_invoice_overdue(&mut self, invoice_id: &str, now: u64) -> Result<(), AgentChainError> {
+        let mut invoice = self.get_invoice(invoice_id).await?;
+        if invoice.status != InvoiceStatus::Open {
+            return Err(AgentChainError::InvoiceFailed("Invoice is not open".to_string()));
+        }
+        if now <= invoice.due_at {
+            return Err(AgentChainError::InvoiceFailed(format!(
+                "Invoice is not due until {}",
+                invoice.due_at
+            )));
+        }
+
+        let mut debtor = self.get_agent(&invoice.debtor_agent).await?;
+        debtor.reputation = debtor.reputation.saturating_sub(OVERDUE_INVOICE_PENALTY);
+        self.agents.insert(&invoice.debtor_agent, debtor)?;
+
+        invoice.status = InvoiceStatus::Overdue;
+        self.invoices.insert(invoice_id, invoice)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raising_a_limit_waits_for_the_debtor() {
+        let mut line = CreditLine::new("creditor", "debtor", "native");
+        line.set_limit(1_000, 10);
+        assert_eq!(line.limit, 0);
+        assert_eq!(line.proposed_limit, Some(1_000));
+        assert!(line.draw(1, 10).is_err());
+
+        line.accept(20).unwrap();
+        assert_eq!(line.limit, 1_000);
+        assert_eq!(line.proposed_limit, None);
+        assert!(line.accept(30).is_err());
+    }
+
+    #[test]
+    fn lowering_a_limit_applies_at_once_and_drops_any_offer() {
+        let mut line = CreditLine::new("creditor", "debtor", "native");
+        line.set_limit(1_000, 10);
+        line.accept(10).unwrap();
+        line.set_limit(2_000, 20);
+        line.set_limit(400, 30);
+        assert_eq!(line.limit, 400);
+        assert_eq!(line.proposed_limit, None);
+    }
+
+    #[test]
+    fn draws_stay_within_the_accepted_limit() {
+        let mut line = CreditLine::new("creditor", "debtor", "native");
+        line.set_limit(100, 0);
+        line.accept(0).unwrap();
+        line.draw(60, 1).unwrap();
+        assert_eq!(line.available(), 40);
+        assert!(matches!(
+            line.draw(41, 2),
+            Err(AgentChainError::CreditLimitExceeded {
+                limit: 100,
+                requested: 101
+            })
+        ));
+        assert_eq!(line.outstanding, 60);
+
+        line.set_limit(50, 3);
+        assert_eq!(line.available(), 0);
+    }
+}
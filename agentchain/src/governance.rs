@@ -0,0 +1,515 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::math::mul_div_ceil;
+use crate::state::{
+    Agent, AgentChainConfig, AgentChainError, AgentChainState, AgentStrategy, TransactionType,
+    NATIVE_ASSET,
+};
+
+/// Counterparty recorded in the ledger for treasury deposits and spends.
+pub const TREASURY: &str = "treasury";
+
+/// Longest delegation chain that is followed when counting votes.
+pub const MAX_DELEGATION_DEPTH: usize = 8;
+
+const BPS: u128 = 10_000;
+
+/// Rules for proposals and voting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GovernanceConfig {
+    /// Seconds a proposal is open for voting.
+    pub voting_period: u64,
+    /// Seconds between the end of voting and the earliest execution.
+    pub timelock: u64,
+    /// Share of the total voting power that must take part for a proposal to pass.
+    pub quorum_bps: u32,
+    /// Voting power an agent needs to create a proposal.
+    pub proposal_threshold: u128,
+}
+
+impl Default for GovernanceConfig {
+    fn default() -> Self {
+        GovernanceConfig {
+            voting_period: 259_200,
+            timelock: 86_400,
+            quorum_bps: 2_000,
+            proposal_threshold: 1,
+        }
+    }
+}
+
+/// What a proposal does once it passes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProposalAction {
+    /// Replaces the application config, except `fungible_applications` and
+    /// `dispute_arbiter`, which governance cannot change.
+    UpdateConfig(AgentChainConfig),
+    /// Pays `amount` of `asset` from the treasury to an agent.
+    TreasurySpend {
+        recipient_agent: String,
+        asset: String,
+        amount: u128,
+    },
+    /// Deactivates an agent and removes its market listings.
+    BanAgent { agent_id: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ProposalStatus {
+    Active,
+    Executed,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Proposal {
+    pub id: String,
+    pub proposer_agent: String,
+    pub description: String,
+    pub action: ProposalAction,
+    pub status: ProposalStatus,
+    pub votes_for: u128,
+    pub votes_against: u128,
+    /// Agents whose voting power has been counted, directly or through a delegate.
+    pub voters: BTreeSet<String>,
+    /// Balance-backed voting power of each active Governance agent when the proposal
+    /// was created.
+    /// Only these agents can vote on it, with this power.
+    pub voting_power_snapshot: BTreeMap<String, u128>,
+    /// Sum of `voting_power_snapshot`, for the quorum.
+    pub total_voting_power: u128,
+    pub created_at: u64,
+    pub voting_ends_at: u64,
+    pub executable_at: u64,
+}
+
+impl Proposal {
+    pub fn has_quorum(&self, quorum_bps: u32) -> bool {
+        let participation = self.votes_for.saturating_add(self.votes_against);
+        mul_div_ceil(self.total_voting_power, quorum_bps as u128, BPS)
+            .is_some_and(|required| participation >= required)
+    }
+}
+
+fn power_overflow() -> AgentChainError {
+    AgentChainError::GovernanceFailed("Voting power overflows".to_string())
+}
+
+fn sum_voting_power(powers: impl IntoIterator<Item = u128>) -> Result<u128, AgentChainError> {
+    powers.into_iter().try_fold(0u128, |total, voting_power| {
+        total.checked_add(voting_power).ok_or_else(power_overflow)
+    })
+}
+
+/// Voting power and delegation setting of an agent's enabled Governance strategy.
+///
+/// The declared power only counts up to the agent's native balance, so every vote is
+/// backed by funds and registering more agents does not add power.
+fn governance_power(agent: &Agent) -> Option<(u128, bool)> {
+    match agent.enabled_strategy("Governance").map(|slot| &slot.strategy) {
+        Some(AgentStrategy::Governance {
+            voting_power,
+            delegation_enabled,
+        }) => Some((
+            (*voting_power).min(agent.balance_of(NATIVE_ASSET)),
+            *delegation_enabled,
+        )),
+        _ => None,
+    }
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    pub async fn get_proposal(&self, proposal_id: &str) -> Result<Proposal, AgentChainError> {
+        self.proposals
+            .get(proposal_id)
+            .await?
+            .ok_or_else(|| AgentChainError::GovernanceFailed("Proposal not found".to_string()))
+    }
+
+    /// Voting power of every active Governance agent.
+    async fn voting_powers(&self) -> Result<BTreeMap<String, u128>, AgentChainError> {
+        let mut powers = BTreeMap::new();
+        self.agents
+            .for_each_index_value(|agent_id, agent| {
+                if agent.is_active {
+                    if let Some((voting_power, _)) = governance_power(&agent) {
+                        powers.insert(agent_id, voting_power);
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(powers)
+    }
+
+    /// Sum of the voting power of active Governance agents.
+    pub async fn total_voting_power(&self) -> Result<u128, AgentChainError> {
+        sum_voting_power(self.voting_powers().await?.into_values())
+    }
+
+    /// Agent that ends up casting `agent_id`'s vote, following delegations.
+    pub async fn resolve_delegate(&self, agent_id: &str) -> Result<String, AgentChainError> {
+        let mut current = agent_id.to_string();
+        for _ in 0..MAX_DELEGATION_DEPTH {
+            match self.delegations.get(&current).await? {
+                Some(delegate) => current = delegate,
+                None => return Ok(current),
+            }
+        }
+        Err(AgentChainError::GovernanceFailed(format!(
+            "Delegation chain from {} is longer than {}",
+            agent_id, MAX_DELEGATION_DEPTH
+        )))
+    }
+
+    /// Delegates `agent_id`'s votes to `delegate_agent`, or clears the delegation.
+    /// Requires `delegation_enabled` on the delegating agent.
+    pub async fn delegate_votes(
+        &mut self,
+        agent_id: &str,
+        delegate_agent: Option<String>,
+    ) -> Result<(), AgentChainError> {
+        let agent = self.get_agent(agent_id).await?;
+        let Some((_, delegation_enabled)) = governance_power(&agent) else {
+            return Err(AgentChainError::GovernanceFailed(
+                "Only Governance agents can delegate".to_string(),
+            ));
+        };
+        let Some(delegate_agent) = delegate_agent else {
+            self.delegations.remove(agent_id)?;
+            return Ok(());
+        };
+        if !delegation_enabled {
+            return Err(AgentChainError::GovernanceFailed(
+                "Delegation is not enabled for this agent".to_string(),
+            ));
+        }
+        let delegate = self.get_agent(&delegate_agent).await?;
+        if governance_power(&delegate).is_none() {
+            return Err(AgentChainError::GovernanceFailed(
+                "Votes can only be delegated to Governance agents".to_string(),
+            ));
+        }
+        if self.resolve_delegate(&delegate_agent).await? == agent_id {
+            return Err(AgentChainError::GovernanceFailed(
+                "Delegation would create a cycle".to_string(),
+            ));
+        }
+        self.delegations.insert(agent_id, delegate_agent)?;
+        Ok(())
+    }
+
+    /// Agents of `voting_powers` whose votes `voter` casts, with their voting power,
+    /// including `voter` itself.
+    async fn represented_voters(
+        &self,
+        voter: &str,
+        voting_powers: &BTreeMap<String, u128>,
+    ) -> Result<Vec<(String, u128)>, AgentChainError> {
+        let mut represented = Vec::new();
+        for (agent_id, voting_power) in voting_powers {
+            // Agents on over-long or broken chains simply do not count.
+            if self.resolve_delegate(agent_id).await.ok().as_deref() == Some(voter) {
+                represented.push((agent_id.clone(), *voting_power));
+            }
+        }
+        Ok(represented)
+    }
+
+    /// Voting power `voter` would cast right now, including delegated power.
+    pub async fn effective_voting_power(&self, voter: &str) -> Result<u128, AgentChainError> {
+        let voting_powers = self.voting_powers().await?;
+        sum_voting_power(
+            self.represented_voters(voter, &voting_powers)
+                .await?
+                .into_iter()
+                .map(|(_, voting_power)| voting_power),
+        )
+    }
+
+    pub async fn create_proposal(
+        &mut self,
+        proposer_agent: &str,
+        description: String,
+        action: ProposalAction,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let config = self.config.get().governance.clone();
+        let voting_power_snapshot = self.voting_powers().await?;
+        let voting_power = sum_voting_power(
+            self.represented_voters(proposer_agent, &voting_power_snapshot)
+                .await?
+                .into_iter()
+                .map(|(_, voting_power)| voting_power),
+        )?;
+        if voting_power < config.proposal_threshold {
+            return Err(AgentChainError::GovernanceFailed(format!(
+                "Proposing requires a voting power of {}, agent has {}",
+                config.proposal_threshold, voting_power
+            )));
+        }
+
+        let mut total_proposals = *self.total_proposals.get();
+        let proposal_id = format!("proposal_{}_{}", now, total_proposals);
+        let voting_ends_at = now + config.voting_period;
+        let proposal = Proposal {
+            id: proposal_id.clone(),
+            proposer_agent: proposer_agent.to_string(),
+            description,
+            action,
+            status: ProposalStatus::Active,
+            votes_for: 0,
+            votes_against: 0,
+            voters: BTreeSet::new(),
+            total_voting_power: sum_voting_power(voting_power_snapshot.values().copied())?,
+            voting_power_snapshot,
+            created_at: now,
+            voting_ends_at,
+            executable_at: voting_ends_at + config.timelock,
+        };
+        self.proposals.insert(&proposal_id, proposal)?;
+
+        total_proposals += 1;
+        self.total_proposals.set(total_proposals);
+
+        Ok(proposal_id)
+    }
+
+    /// Casts `voter`'s vote with its own power and the power delegated to it, as
+    /// recorded when the proposal was created. Agents whose power has already been
+    /// counted are skipped, so changing delegations mid-vote cannot count the same power
+    /// twice. Returns the weight cast.
+    pub async fn cast_vote(
+        &mut self,
+        proposal_id: &str,
+        voter: &str,
+        support: bool,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        let mut proposal = self.get_proposal(proposal_id).await?;
+        if proposal.status != ProposalStatus::Active
+            || now >= proposal.voting_ends_at
+        {
+            return Err(AgentChainError::GovernanceFailed("Voting is closed".to_string()));
+        }
+        if self.delegations.get(voter).await?.is_some() {
+            return Err(AgentChainError::GovernanceFailed(
+                "Agent has delegated its votes".to_string(),
+            ));
+        }
+
+        let mut weight = 0u128;
+        for (agent_id, voting_power) in self
+            .represented_voters(voter, &proposal.voting_power_snapshot)
+            .await?
+        {
+            if proposal.voters.insert(agent_id) {
+                weight = weight.checked_add(voting_power).ok_or_else(power_overflow)?;
+            }
+        }
+        if weight == 0 {
+            return Err(AgentChainError::GovernanceFailed(
+                "Agent has no voting power left to cast".to_string(),
+            ));
+        }
+        let tally = if support {
+            &mut proposal.votes_for
+        } else {
+            &mut proposal.votes_against
+        };
+        *tally = tally.checked_add(weight).ok_or_else(power_overflow)?;
+        self.proposals.insert(proposal_id, proposal)?;
+        Ok(weight)
+    }
+
+    /// Settles a proposal after its timelock: executes it if it reached quorum with a
+    /// majority in favor, rejects it otherwise. Returns whether it was executed.
+    pub async fn execute_proposal(&mut self, proposal_id: &str, now: u64) -> Result<bool, AgentChainError> {
+        let mut proposal = self.get_proposal(proposal_id).await?;
+        if proposal.status != ProposalStatus::Active {
+            return Err(AgentChainError::GovernanceFailed(
+                "Proposal has already been settled".to_string(),
+            ));
+        }
+        if now < proposal.executable_at {
+            return Err(AgentChainError::GovernanceFailed(format!(
+                "Proposal can be executed from {}",
+                proposal.executable_at
+            )));
+        }
+
+        let quorum_bps = self.config.get().governance.quorum_bps;
+        let passed = proposal.has_quorum(quorum_bps) && proposal.votes_for > proposal.votes_against;
+        if passed {
+            self.apply_proposal_action(&proposal.action, now).await?;
+            proposal.status = ProposalStatus::Executed;
+        } else {
+            proposal.status = ProposalStatus::Rejected;
+        }
+        self.proposals.insert(proposal_id, proposal)?;
+        Ok(passed)
+    }
+
+    async fn apply_proposal_action(&mut self, action: &ProposalAction, now: u64) -> Result<(), AgentChainError> {
+        match action {
+            ProposalAction::UpdateConfig(config) => {
+                let current = self.config.get();
+                let config = AgentChainConfig {
+                    fungible_applications: current.fungible_applications.clone(),
+                    dispute_arbiter: current.dispute_arbiter.clone(),
+                    ..config.clone()
+                };
+                self.config.set(config);
+            }
+            ProposalAction::TreasurySpend {
+                recipient_agent,
+                asset,
+                amount,
+            } => {
+                let balance = self.treasury.get(asset).await?.unwrap_or(0);
+                if balance < *amount {
+                    return Err(AgentChainError::InsufficientBalance {
+                        required: *amount,
+                        available: balance,
+                    });
+                }
+                let mut recipient = self.get_agent(recipient_agent).await?;
+                recipient.credit(asset, *amount);
+                self.agents.insert(recipient_agent, recipient)?;
+                self.treasury.insert(asset, balance - amount)?;
+                self.record_transaction(
+                    TREASURY,
+                    recipient_agent,
+                    asset,
+                    *amount,
+                    TransactionType::TreasurySpend,
+                    now,
+                )
+                .await?;
+            }
+            ProposalAction::BanAgent { agent_id } => {
+                let mut agent = self.get_agent(agent_id).await?;
+                agent.is_active = false;
+                self.agents.insert(agent_id, agent)?;
+                self.delegations.remove(agent_id)?;
+                self.delist_agent(agent_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves `amount` of `asset` from an agent into the treasury.
+    pub async fn fund_treasury(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        agent.debit(asset, amount)?;
+        self.agents.insert(agent_id, agent)?;
+
+        let balance = self.treasury.get(asset).await?.unwrap_or(0);
+        self.treasury.insert(asset, balance + amount)?;
+
+        self.record_transaction(agent_id, TREASURY, asset, amount, TransactionType::TreasuryDeposit, now)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::StrategySlot;
+
+    fn proposal(votes_for: u128, votes_against: u128, total_voting_power: u128) -> Proposal {
+        Proposal {
+            id: "proposal_1".to_string(),
+            proposer_agent: "proposer".to_string(),
+            description: String::new(),
+            action: ProposalAction::BanAgent {
+                agent_id: "spammer".to_string(),
+            },
+            status: ProposalStatus::Active,
+            votes_for,
+            votes_against,
+            voters: BTreeSet::new(),
+            voting_power_snapshot: BTreeMap::new(),
+            total_voting_power,
+            created_at: 0,
+            voting_ends_at: 0,
+            executable_at: 0,
+        }
+    }
+
+    #[test]
+    fn quorum_counts_votes_on_both_sides() {
+        assert!(!proposal(10, 9, 100).has_quorum(2_000));
+        assert!(proposal(10, 10, 100).has_quorum(2_000));
+        assert!(proposal(0, 20, 100).has_quorum(2_000));
+        // The required participation rounds up.
+        assert!(!proposal(20, 0, 101).has_quorum(2_000));
+        assert!(proposal(21, 0, 101).has_quorum(2_000));
+    }
+
+    #[test]
+    fn quorum_math_does_not_overflow() {
+        assert!(proposal(u128::MAX, u128::MAX, u128::MAX).has_quorum(10_000));
+        assert!(!proposal(u128::MAX / 2, 0, u128::MAX).has_quorum(10_000));
+        assert!(proposal(u128::MAX / 5 + 1, 0, u128::MAX).has_quorum(2_000));
+    }
+
+    fn governance_agent(id: &str, voting_power: u128, native_balance: u128) -> Agent {
+        let mut agent = crate::state::tests::agent(id);
+        agent.strategies.push(StrategySlot {
+            strategy: AgentStrategy::Governance {
+                voting_power,
+                delegation_enabled: false,
+            },
+            allocation_bps: 10_000,
+            enabled: true,
+        });
+        agent.credit(NATIVE_ASSET, native_balance);
+        agent
+    }
+
+    #[test]
+    fn declared_voting_power_is_capped_at_the_native_balance() {
+        assert_eq!(governance_power(&governance_agent("a", 500, 1_000)), Some((500, false)));
+        assert_eq!(governance_power(&governance_agent("b", 500, 200)), Some((200, false)));
+        assert_eq!(governance_power(&governance_agent("c", 500, 0)), Some((0, false)));
+        assert_eq!(governance_power(&crate::state::tests::agent("d")), None);
+    }
+
+    #[test]
+    fn unbacked_agents_cannot_reach_quorum() {
+        let backed = governance_agent("backed", 1_000, 1_000);
+        let sybils: Vec<_> = (0..100)
+            .map(|i| governance_agent(&format!("sybil_{}", i), 1_000_000, 0))
+            .collect();
+        let powers: BTreeMap<_, _> = std::iter::once(&backed)
+            .chain(&sybils)
+            .filter_map(|agent| Some((agent.id.clone(), governance_power(agent)?.0)))
+            .collect();
+
+        let total = sum_voting_power(powers.values().copied()).unwrap();
+        assert_eq!(total, 1_000);
+        let sybil_votes = sum_voting_power(sybils.iter().map(|agent| powers[&agent.id])).unwrap();
+        assert!(!proposal(sybil_votes, 0, total).has_quorum(2_000));
+        assert!(proposal(powers["backed"], 0, total).has_quorum(2_000));
+    }
+
+    #[test]
+    fn voting_power_sums_fail_on_overflow() {
+        assert_eq!(sum_voting_power([1, 2, 3]).unwrap(), 6);
+        assert_eq!(sum_voting_power([]).unwrap(), 0);
+        assert!(matches!(
+            sum_voting_power([u128::MAX, 1]),
+            Err(AgentChainError::GovernanceFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,322 @@
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::math::mul_div;
+use crate::state::{Agent, AgentChainError, AgentChainState, ServiceRequest, TransactionType};
+
+/// Counterparty recorded in the ledger for premiums and payouts.
+pub const INSURANCE_POOL: &str = "insurance_pool";
+
+const BPS: u128 = 10_000;
+
+/// Pricing and coverage of the opt-in service insurance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsuranceConfig {
+    /// Premium charged for a provider with a perfect record.
+    pub base_premium_bps: u32,
+    /// Share of the provider's failure rate added to the premium.
+    pub failure_rate_weight_bps: u32,
+    /// Premium added for a provider with no reputation, scaled down linearly up to
+    /// the maximum reputation.
+    pub reputation_weight_bps: u32,
+    pub max_premium_bps: u32,
+    /// Share of the payment covered. A claim pays at most this much, and never more
+    /// than the requester lost.
+    pub coverage_bps: u32,
+}
+
+impl Default for InsuranceConfig {
+    fn default() -> Self {
+        InsuranceConfig {
+            base_premium_bps: 100,
+            failure_rate_weight_bps: 5_000,
+            reputation_weight_bps: 500,
+            max_premium_bps: 2_500,
+            coverage_bps: 5_000,
+        }
+    }
+}
+
+/// Pooled premiums for one asset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsurancePool {
+    pub asset: String,
+    pub balance: u128,
+    /// Coverage of insured requests that are still open.
+    pub active_coverage: u128,
+    pub premiums_collected: u128,
+    pub claims_paid: u128,
+    pub policies_written: u64,
+    pub claims_count: u64,
+}
+
+impl InsurancePool {
+    fn new(asset: &str) -> Self {
+        InsurancePool {
+            asset: asset.to_string(),
+            balance: 0,
+            active_coverage: 0,
+            premiums_collected: 0,
+            claims_paid: 0,
+            policies_written: 0,
+            claims_count: 0,
+        }
+    }
+
+    /// Pool balance relative to open coverage; `None` when nothing is covered.
+    pub fn coverage_ratio_bps(&self) -> Option<u128> {
+        if self.active_coverage == 0 {
+            None
+        } else {
+            Some(mul_div(self.balance, BPS, self.active_coverage).unwrap_or(u128::MAX))
+        }
+    }
+
+    /// Pays a claim for `net_loss` under `cover`, capped by the coverage and by what the
+    /// pool holds. Returns the payout.
+    pub fn pay_claim(&mut self, cover: &InsuranceCover, net_loss: u128) -> u128 {
+        let payout = cover.coverage.min(net_loss).min(self.balance);
+        self.balance -= payout;
+        self.claims_paid += payout;
+        self.claims_count += 1;
+        payout
+    }
+
+    /// Closes `cover`, releasing its coverage. When there is a `claim`, the requester's
+    /// `net_loss` is paid under the cover and the payout returned.
+    pub fn settle(
+        &mut self,
+        cover: &mut InsuranceCover,
+        claim: Option<ClaimReason>,
+        net_loss: u128,
+    ) -> Option<u128> {
+        cover.settled = true;
+        self.active_coverage = self.active_coverage.saturating_sub(cover.coverage);
+        let reason = claim.filter(|_| net_loss > 0)?;
+        let payout = self.pay_claim(cover, net_loss);
+        cover.claim = Some(InsuranceClaim { reason, payout });
+        Some(payout)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsuranceCover {
+    pub premium: u128,
+    pub coverage: u128,
+    pub claim: Option<InsuranceClaim>,
+    pub settled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsuranceClaim {
+    pub reason: ClaimReason,
+    pub payout: u128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ClaimReason {
+    /// The arbiter ruled for the requester on a disputed request.
+    DisputeLost,
+    /// The provider reported that it failed to deliver the service.
+    ServiceFailed,
+}
+
+impl InsuranceConfig {
+    /// Premium rate for requests served by `provider`, from its reputation and failure rate.
+    pub fn premium_bps(&self, provider: &Agent) -> u128 {
+        let total_services = provider.services_completed + provider.services_failed;
+        let failure_rate_bps = if total_services > 0 {
+            mul_div(provider.services_failed as u128, BPS, total_services as u128).unwrap_or(BPS)
+        } else {
+            0
+        };
+        let reputation_gap = 1000u128.saturating_sub(provider.reputation as u128);
+
+        let premium_bps = self.base_premium_bps as u128
+            + failure_rate_bps * self.failure_rate_weight_bps as u128 / BPS
+            + reputation_gap * self.reputation_weight_bps as u128 / 1000;
+        premium_bps.min(self.max_premium_bps as u128)
+    }
+
+    pub fn quote(&self, provider: &Agent, payment: u128) -> (u128, u128) {
+        let premium = mul_div(payment, self.premium_bps(provider), BPS).unwrap_or(u128::MAX);
+        let coverage = mul_div(payment, self.coverage_bps as u128, BPS).unwrap_or(u128::MAX);
+        (premium, coverage)
+    }
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    async fn load_insurance_pool(&self, asset: &str) -> Result<InsurancePool, AgentChainError> {
+        Ok(self
+            .insurance_pools
+            .get(asset)
+            .await?
+            .unwrap_or_else(|| InsurancePool::new(asset)))
+    }
+
+    /// Charges the requester the premium for insuring a payment to `provider` and adds
+    /// the coverage to the pool.
+    pub(crate) async fn underwrite(
+        &mut self,
+        requester: &mut Agent,
+        provider: &Agent,
+        asset: &str,
+        payment: u128,
+        now: u64,
+    ) -> Result<InsuranceCover, AgentChainError> {
+        let (premium, coverage) = self.config.get().insurance.quote(provider, payment);
+        requester.debit(asset, premium)?;
+
+        let mut pool = self.load_insurance_pool(asset).await?;
+        pool.balance += premium;
+        pool.premiums_collected += premium;
+        pool.active_coverage += coverage;
+        pool.policies_written += 1;
+        self.insurance_pools.insert(asset, pool)?;
+
+        self.record_transaction(
+            &requester.id,
+            INSURANCE_POOL,
+            asset,
+            premium,
+            TransactionType::InsurancePremium,
+            now,
+        )
+        .await?;
+
+        Ok(InsuranceCover {
+            premium,
+            coverage,
+            claim: None,
+            settled: false,
+        })
+    }
+
+    /// Closes the request's cover. When the request failed, on the provider's report or
+    /// a dispute ruled for the requester, the requester is paid its net loss: what was
+    /// released to the provider side and not refunded, up to the coverage and what the
+    /// pool holds. The escrow refund is not part of the loss.
+    pub(crate) async fn settle_insurance(
+        &mut self,
+        request: &mut ServiceRequest,
+        claim: Option<ClaimReason>,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let Some(mut cover) = request.insurance.take() else {
+            return Ok(());
+        };
+        if cover.settled {
+            request.insurance = Some(cover);
+            return Ok(());
+        }
+
+        let mut pool = self.load_insurance_pool(&request.asset).await?;
+        if let Some(payout) = pool.settle(&mut cover, claim, request.refundable()) {
+            let mut requester = self.get_agent(&request.requester_agent).await?;
+            requester.credit(&request.asset, payout);
+            self.agents.insert(&request.requester_agent, requester)?;
+
+            self.record_linked_transaction(
+                INSURANCE_POOL,
+                &request.requester_agent,
+                &request.asset,
+                payout,
+                TransactionType::InsurancePayout,
+                Some(&request.id),
+                None,
+                now,
+            )
+            .await?;
+        }
+
+        self.insurance_pools.insert(&request.asset, pool)?;
+        request.insurance = Some(cover);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(reputation: u64, completed: u64, failed: u64) -> Agent {
+        Agent {
+            reputation,
+            services_completed: completed,
+            services_failed: failed,
+            ..crate::state::tests::agent("provider")
+        }
+    }
+
+    fn cover(coverage: u128) -> InsuranceCover {
+        InsuranceCover {
+            premium: 0,
+            coverage,
+            claim: None,
+            settled: false,
+        }
+    }
+
+    #[test]
+    fn premiums_grow_with_failures_and_low_reputation() {
+        let config = InsuranceConfig::default();
+        assert_eq!(config.premium_bps(&provider(1000, 10, 0)), 100);
+        // 20% failures add 1 000 bps, 500 reputation points short add 250 bps.
+        assert_eq!(config.premium_bps(&provider(500, 8, 2)), 1_350);
+        assert_eq!(config.premium_bps(&provider(0, 0, 10)), 2_500);
+    }
+
+    #[test]
+    fn quotes_price_premium_and_coverage_from_the_payment() {
+        let config = InsuranceConfig::default();
+        assert_eq!(config.quote(&provider(500, 8, 2), 10_000), (1_350, 5_000));
+        let (premium, coverage) = config.quote(&provider(1000, 0, 0), u128::MAX);
+        assert_eq!(premium, u128::MAX / 100);
+        assert_eq!(coverage, u128::MAX / 2);
+    }
+
+    #[test]
+    fn claims_pay_the_net_loss_up_to_coverage_and_pool_balance() {
+        let mut pool = InsurancePool::new("native");
+        pool.balance = 1_000;
+
+        assert_eq!(pool.pay_claim(&cover(500), 200), 200);
+        assert_eq!(pool.pay_claim(&cover(500), 900), 500);
+        assert_eq!(pool.pay_claim(&cover(500), 900), 300);
+        assert_eq!(pool.balance, 0);
+        assert_eq!(pool.claims_paid, 1_000);
+        assert_eq!(pool.claims_count, 3);
+    }
+
+    #[test]
+    fn settling_pays_a_claim_only_for_a_failed_request_with_a_loss() {
+        let mut pool = InsurancePool::new("native");
+        pool.balance = 1_000;
+        pool.active_coverage = 1_500;
+
+        let mut completed = cover(500);
+        assert_eq!(pool.settle(&mut completed, None, 300), None);
+        assert!(completed.settled && completed.claim.is_none());
+
+        let mut nothing_lost = cover(500);
+        assert_eq!(pool.settle(&mut nothing_lost, Some(ClaimReason::ServiceFailed), 0), None);
+        assert!(nothing_lost.claim.is_none());
+
+        let mut failed = cover(500);
+        assert_eq!(pool.settle(&mut failed, Some(ClaimReason::ServiceFailed), 800), Some(500));
+        let claim = failed.claim.unwrap();
+        assert_eq!(claim.reason, ClaimReason::ServiceFailed);
+        assert_eq!(claim.payout, 500);
+        assert_eq!(pool.balance, 500);
+        assert_eq!(pool.active_coverage, 0);
+    }
+
+    #[test]
+    fn coverage_ratio_compares_balance_to_open_coverage() {
+        let mut pool = InsurancePool::new("native");
+        assert_eq!(pool.coverage_ratio_bps(), None);
+        pool.balance = 300;
+        pool.active_coverage = 1_200;
+        assert_eq!(pool.coverage_ratio_bps(), Some(2_500));
+    }
+}
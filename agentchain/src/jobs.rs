@@ -0,0 +1,229 @@
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Agent, AgentChainError, AgentChainState, ServiceTerms};
+
+/// Counterparty recorded against the requester's spending policy for job payments,
+/// which are escrowed before a provider is known.
+pub const JOB_BOARD: &str = "job_board";
+
+/// A service request without a provider, open to any eligible agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenJob {
+    pub id: String,
+    pub requester_agent: String,
+    pub service_type: String,
+    pub parameters: String,
+    pub asset: String,
+    pub payment: u128,
+    pub min_reputation: u64,
+    /// Amount of `asset` the provider must lock as a bond when claiming the job.
+    pub required_stake: u128,
+    pub status: JobStatus,
+    pub claimed_by: Option<String>,
+    pub request_id: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum JobStatus {
+    Open,
+    Claimed,
+    Cancelled,
+}
+
+impl OpenJob {
+    /// Why `agent` cannot claim this job, if it cannot.
+    pub fn ineligibility(&self, agent: &Agent) -> Option<String> {
+        if !agent.is_active {
+            Some("agent is inactive".to_string())
+        } else if agent.id == self.requester_agent {
+            Some("agent posted the job".to_string())
+        } else if agent.reputation < self.min_reputation {
+            Some(format!(
+                "reputation {} is below the required {}",
+                agent.reputation, self.min_reputation
+            ))
+        } else if agent.balance_of(&self.asset) < self.required_stake {
+            Some(format!(
+                "balance cannot cover the required stake of {}",
+                self.required_stake
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    /// Posts a job for any eligible provider to claim. The payment is taken from the
+    /// requester right away, moves into the request's escrow when the job is claimed and
+    /// is returned if the job is cancelled.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn post_job(
+        &mut self,
+        requester_agent: &str,
+        service_type: String,
+        parameters: String,
+        asset: String,
+        payment: u128,
+        min_reputation: u64,
+        required_stake: u128,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        self.charge_requester(requester_agent, JOB_BOARD, &service_type, &asset, payment, now)
+            .await?;
+
+        let mut total_jobs = *self.total_jobs.get();
+        let job_id = format!("job_{}_{}", now, total_jobs);
+        let job = OpenJob {
+            id: job_id.clone(),
+            requester_agent: requester_agent.to_string(),
+            service_type,
+            parameters,
+            asset,
+            payment,
+            min_reputation,
+            required_stake,
+            status: JobStatus::Open,
+            claimed_by: None,
+            request_id: None,
+            created_at: now,
+        };
+        self.open_jobs.insert(&job_id, job)?;
+
+        total_jobs += 1;
+        self.total_jobs.set(total_jobs);
+
+        Ok(job_id)
+    }
+
+    pub async fn get_job(&self, job_id: &str) -> Result<OpenJob, AgentChainError> {
+        self.open_jobs
+            .get(job_id)
+            .await?
+            .ok_or_else(|| AgentChainError::JobFailed("Job not found".to_string()))
+    }
+
+    /// Assigns an open job to `provider_agent` and turns it into a service request that
+    /// escrows the job's payment, locking the provider's stake.
+    ///
+    /// Operations on a chain run one at a time, so once a claim succeeds the job is no
+    /// longer open and any competing claim fails.
+    pub async fn claim_job(
+        &mut self,
+        job_id: &str,
+        provider_agent: &str,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let mut job = self.get_job(job_id).await?;
+        if job.status != JobStatus::Open {
+            return Err(AgentChainError::JobFailed("Job is no longer open".to_string()));
+        }
+        let provider = self.get_agent(provider_agent).await?;
+        if let Some(reason) = job.ineligibility(&provider) {
+            return Err(AgentChainError::NotEligible {
+                agent_id: provider_agent.to_string(),
+                reason,
+            });
+        }
+
+        let terms = ServiceTerms {
+            service_type: job.service_type.clone(),
+            parameters: job.parameters.clone(),
+            asset: job.asset.clone(),
+            payment: job.payment,
+            milestones: Vec::new(),
+            insured: false,
+        };
+        let request_id = self
+            .open_service_request(job.requester_agent.clone(), provider_agent.to_string(), terms, now)
+            .await?;
+        if job.required_stake > 0 {
+            self.lock_provider_stake(&request_id, job.required_stake).await?;
+        }
+
+        job.status = JobStatus::Claimed;
+        job.claimed_by = Some(provider_agent.to_string());
+        job.request_id = Some(request_id.clone());
+        self.open_jobs.insert(job_id, job)?;
+
+        Ok(request_id)
+    }
+
+    /// Withdraws an unclaimed job and returns its payment to the requester.
+    pub async fn cancel_job(&mut self, job_id: &str) -> Result<(), AgentChainError> {
+        let mut job = self.get_job(job_id).await?;
+        if job.status != JobStatus::Open {
+            return Err(AgentChainError::JobFailed("Job is no longer open".to_string()));
+        }
+        let mut requester = self.get_agent(&job.requester_agent).await?;
+        requester.credit(&job.asset, job.payment);
+        self.agents.insert(&job.requester_agent, requester)?;
+        job.status = JobStatus::Cancelled;
+        self.open_jobs.insert(job_id, job)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job() -> OpenJob {
+        OpenJob {
+            id: "job".to_string(),
+            requester_agent: "requester".to_string(),
+            service_type: "audit".to_string(),
+            parameters: String::new(),
+            asset: "native".to_string(),
+            payment: 100,
+            min_reputation: 50,
+            required_stake: 20,
+            status: JobStatus::Open,
+            claimed_by: None,
+            request_id: None,
+            created_at: 0,
+        }
+    }
+
+    fn provider() -> Agent {
+        let mut agent = crate::state::tests::agent("provider");
+        agent.credit("native", 20);
+        agent
+    }
+
+    #[test]
+    fn eligible_providers_meet_every_requirement() {
+        assert_eq!(job().ineligibility(&provider()), None);
+    }
+
+    #[test]
+    fn ineligible_providers_are_told_why() {
+        let job = job();
+
+        let mut inactive = provider();
+        inactive.is_active = false;
+        assert_eq!(job.ineligibility(&inactive).as_deref(), Some("agent is inactive"));
+
+        let requester = Agent {
+            id: "requester".to_string(),
+            ..provider()
+        };
+        assert_eq!(job.ineligibility(&requester).as_deref(), Some("agent posted the job"));
+
+        let mut unknown = provider();
+        unknown.reputation = 49;
+        assert_eq!(
+            job.ineligibility(&unknown).as_deref(),
+            Some("reputation 49 is below the required 50")
+        );
+
+        let mut broke = provider();
+        broke.debit("native", 1).unwrap();
+        assert_eq!(
+            job.ineligibility(&broke).as_deref(),
+            Some("balance cannot cover the required stake of 20")
+        );
+    }
+}
@@ -0,0 +1,649 @@
+use std::collections::BTreeMap;
+
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::math::{mul_div, mul_div_ceil};
+use crate::state::{AgentChainError, AgentChainState};
+
+/// Fixed-point scale of `LendingPool::borrow_index`.
+pub const INDEX_SCALE: u128 = 1_000_000_000_000_000_000;
+
+pub const SECONDS_PER_YEAR: u128 = 31_536_000;
+
+const BPS: u128 = 10_000;
+
+/// Interest and collateral parameters shared by all lending pools.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LendingConfig {
+    /// Yearly borrow rate when nothing is borrowed.
+    pub base_rate_bps: u32,
+    /// Yearly rate added on top of the base rate at 100% utilization.
+    pub utilization_slope_bps: u32,
+    /// Share of collateral that may be borrowed against.
+    pub collateral_factor_bps: u32,
+    /// Debt-to-collateral ratio above which a position can be liquidated.
+    pub liquidation_threshold_bps: u32,
+    /// Extra collateral a liquidator receives on top of the debt it repays.
+    pub liquidation_bonus_bps: u32,
+    /// Oracle feed quoting each asset's price in a shared unit. Collateral in one asset
+    /// can only back a loan in another when both have a feed.
+    pub price_feeds: BTreeMap<String, String>,
+    /// Oldest feed value, in seconds, that collateral may be valued with.
+    pub max_price_age: u64,
+}
+
+impl Default for LendingConfig {
+    fn default() -> Self {
+        LendingConfig {
+            base_rate_bps: 200,
+            utilization_slope_bps: 2_000,
+            collateral_factor_bps: 6_667,
+            liquidation_threshold_bps: 8_000,
+            liquidation_bonus_bps: 500,
+            price_feeds: BTreeMap::new(),
+            max_price_age: 3_600,
+        }
+    }
+}
+
+/// A single-asset money market. Borrowers post collateral either in the asset they borrow,
+/// whose value never moves against the debt, or in another asset valued through the
+/// configured price feeds, in which case price moves can make the position liquidatable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LendingPool {
+    pub asset: String,
+    /// What depositors are owed, including accrued interest.
+    pub total_deposits: u128,
+    pub total_shares: u128,
+    /// Sum of `LendingPosition::scaled_debt` over all borrowers.
+    pub total_scaled_debt: u128,
+    pub borrow_index: u128,
+    /// Collateral held in this pool's asset, whichever pool's loans it backs.
+    pub total_collateral: u128,
+    pub last_accrued_at: u64,
+}
+
+impl LendingPool {
+    fn new(asset: &str, now: u64) -> Self {
+        LendingPool {
+            asset: asset.to_string(),
+            total_deposits: 0,
+            total_shares: 0,
+            total_scaled_debt: 0,
+            borrow_index: INDEX_SCALE,
+            total_collateral: 0,
+            last_accrued_at: now,
+        }
+    }
+
+    pub fn total_borrowed(&self) -> u128 {
+        mul_div(self.total_scaled_debt, self.borrow_index, INDEX_SCALE).unwrap_or(u128::MAX)
+    }
+
+    pub fn available_liquidity(&self) -> u128 {
+        self.total_deposits.saturating_sub(self.total_borrowed())
+    }
+
+    pub fn utilization_bps(&self) -> u128 {
+        if self.total_deposits == 0 {
+            0
+        } else {
+            mul_div(self.total_borrowed(), BPS, self.total_deposits)
+                .unwrap_or(BPS)
+                .min(BPS)
+        }
+    }
+
+    pub fn borrow_rate_bps(&self, config: &LendingConfig) -> u128 {
+        config.base_rate_bps as u128 + config.utilization_slope_bps as u128 * self.utilization_bps() / BPS
+    }
+
+    /// Grows outstanding debt by the interest owed since the last accrual and credits it
+    /// to depositors.
+    pub fn accrue(&mut self, config: &LendingConfig, now: u64) {
+        let elapsed = now.saturating_sub(self.last_accrued_at) as u128;
+        if elapsed > 0 && self.total_scaled_debt > 0 {
+            let borrowed_before = self.total_borrowed();
+            let growth = mul_div(
+                self.borrow_index,
+                self.borrow_rate_bps(config) * elapsed,
+                BPS * SECONDS_PER_YEAR,
+            )
+            .unwrap_or(u128::MAX);
+            self.borrow_index = self.borrow_index.saturating_add(growth);
+            self.total_deposits = self
+                .total_deposits
+                .saturating_add(self.total_borrowed() - borrowed_before);
+        }
+        self.last_accrued_at = now;
+    }
+}
+
+/// One agent's deposits, collateral and debt in a single pool.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LendingPosition {
+    pub agent_id: String,
+    pub asset: String,
+    pub deposit_shares: u128,
+    /// Asset the collateral is held in, chosen when collateral is first added.
+    pub collateral_asset: String,
+    pub collateral: u128,
+    pub scaled_debt: u128,
+}
+
+impl LendingPosition {
+    pub fn debt(&self, pool: &LendingPool) -> u128 {
+        // Round up so that borrowers never repay less than they owe.
+        mul_div_ceil(self.scaled_debt, pool.borrow_index, INDEX_SCALE).unwrap_or(u128::MAX)
+    }
+
+    pub fn deposit_value(&self, pool: &LendingPool) -> u128 {
+        if pool.total_shares == 0 {
+            0
+        } else {
+            mul_div(self.deposit_shares, pool.total_deposits, pool.total_shares).unwrap_or(u128::MAX)
+        }
+    }
+
+    /// Whether the debt has grown past the liquidation threshold of `collateral_value`,
+    /// the collateral valued in the borrowed asset.
+    pub fn is_liquidatable(
+        &self,
+        pool: &LendingPool,
+        config: &LendingConfig,
+        collateral_value: u128,
+    ) -> bool {
+        let threshold = mul_div(collateral_value, config.liquidation_threshold_bps as u128, BPS)
+            .unwrap_or(u128::MAX);
+        self.debt(pool) > threshold
+    }
+
+    /// Collateral worth `value` of the borrowed asset, at the price implied by
+    /// `collateral_value`, capped at what the position holds.
+    pub fn collateral_worth(&self, value: u128, collateral_value: u128) -> u128 {
+        if collateral_value == 0 {
+            return self.collateral;
+        }
+        mul_div(value, self.collateral, collateral_value)
+            .unwrap_or(u128::MAX)
+            .min(self.collateral)
+    }
+}
+
+/// Converts `amount` of an asset priced at `from_price` into an asset priced at
+/// `to_price`, both quoted in the same unit.
+pub fn convert_value(amount: u128, from_price: u128, to_price: u128) -> Option<u128> {
+    if to_price == 0 {
+        return None;
+    }
+    mul_div(amount, from_price, to_price)
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    pub fn lending_position_key(agent_id: &str, asset: &str) -> String {
+        format!("{}_{}", agent_id, asset)
+    }
+
+    async fn load_lending_pool(&mut self, asset: &str, now: u64) -> Result<LendingPool, AgentChainError> {
+        let mut pool = self
+            .lending_pools
+            .get(asset)
+            .await?
+            .unwrap_or_else(|| LendingPool::new(asset, now));
+        pool.accrue(&self.config.get().lending, now);
+        Ok(pool)
+    }
+
+    async fn load_lending_position(
+        &self,
+        agent_id: &str,
+        asset: &str,
+    ) -> Result<LendingPosition, AgentChainError> {
+        let key = Self::lending_position_key(agent_id, asset);
+        Ok(self.lending_positions.get(&key).await?.unwrap_or_else(|| LendingPosition {
+            agent_id: agent_id.to_string(),
+            asset: asset.to_string(),
+            collateral_asset: asset.to_string(),
+            ..LendingPosition::default()
+        }))
+    }
+
+    /// Latest price of `asset` from its configured feed, if it is recent enough.
+    async fn asset_price(&self, asset: &str, now: u64) -> Result<u128, AgentChainError> {
+        let config = &self.config.get().lending;
+        let feed_id = config.price_feeds.get(asset).ok_or_else(|| {
+            AgentChainError::LendingFailed(format!("No price feed is configured for {}", asset))
+        })?;
+        let price = self.latest_feed_value(feed_id).await?;
+        if now.saturating_sub(price.aggregated_at) > config.max_price_age || price.value == 0 {
+            return Err(AgentChainError::LendingFailed(format!(
+                "Price of {} is not fresh",
+                asset
+            )));
+        }
+        Ok(price.value)
+    }
+
+    /// Value of the position's collateral in the borrowed asset.
+    pub async fn collateral_value(
+        &self,
+        position: &LendingPosition,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        if position.collateral_asset == position.asset || position.collateral == 0 {
+            return Ok(position.collateral);
+        }
+        let collateral_price = self.asset_price(&position.collateral_asset, now).await?;
+        let debt_price = self.asset_price(&position.asset, now).await?;
+        Ok(convert_value(position.collateral, collateral_price, debt_price).unwrap_or(u128::MAX))
+    }
+
+    /// Adds `added` to and takes `removed` from the collateral counted by the pool of
+    /// `collateral_asset`, which is `pool` itself for same-asset collateral.
+    async fn track_collateral(
+        &mut self,
+        pool: &mut LendingPool,
+        collateral_asset: &str,
+        added: u128,
+        removed: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let update = |pool: &mut LendingPool| {
+            let total = pool.total_collateral.checked_add(added).ok_or_else(|| {
+                AgentChainError::LendingFailed("Collateral is too large".to_string())
+            })?;
+            pool.total_collateral = total.saturating_sub(removed);
+            Ok(())
+        };
+        if collateral_asset == pool.asset {
+            return update(pool);
+        }
+        let mut collateral_pool = self.load_lending_pool(collateral_asset, now).await?;
+        update(&mut collateral_pool)?;
+        self.lending_pools.insert(collateral_asset, collateral_pool)?;
+        Ok(())
+    }
+
+    fn save_lending(
+        &mut self,
+        pool: LendingPool,
+        position: LendingPosition,
+    ) -> Result<(), AgentChainError> {
+        let key = Self::lending_position_key(&position.agent_id, &position.asset);
+        self.lending_positions.insert(&key, position)?;
+        self.lending_pools.insert(&pool.asset.clone(), pool)?;
+        Ok(())
+    }
+
+    /// Moves `amount` from the agent's balance into the pool in exchange for pool shares.
+    pub async fn deposit_to_pool(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let mut pool = self.load_lending_pool(asset, now).await?;
+        let mut position = self.load_lending_position(agent_id, asset).await?;
+
+        agent.debit(asset, amount)?;
+        let shares = if pool.total_shares == 0 || pool.total_deposits == 0 {
+            amount
+        } else {
+            mul_div(amount, pool.total_shares, pool.total_deposits).ok_or_else(|| {
+                AgentChainError::LendingFailed("Deposit is too large".to_string())
+            })?
+        };
+        pool.total_deposits = pool.total_deposits.checked_add(amount).ok_or_else(|| {
+            AgentChainError::LendingFailed("Deposit is too large".to_string())
+        })?;
+        pool.total_shares += shares;
+        position.deposit_shares += shares;
+
+        self.agents.insert(agent_id, agent)?;
+        self.save_lending(pool, position)?;
+        Ok(shares)
+    }
+
+    /// Redeems pool shares for their current value, as long as the pool has enough
+    /// unborrowed liquidity.
+    pub async fn withdraw_from_pool(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        shares: u128,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let mut pool = self.load_lending_pool(asset, now).await?;
+        let mut position = self.load_lending_position(agent_id, asset).await?;
+
+        if shares > position.deposit_shares {
+            return Err(AgentChainError::LendingFailed(format!(
+                "Position holds {} shares, requested {}",
+                position.deposit_shares, shares
+            )));
+        }
+        let amount = mul_div(shares, pool.total_deposits, pool.total_shares).unwrap_or(0);
+        if amount > pool.available_liquidity() {
+            return Err(AgentChainError::InsufficientBalance {
+                required: amount,
+                available: pool.available_liquidity(),
+            });
+        }
+
+        pool.total_deposits -= amount;
+        pool.total_shares -= shares;
+        position.deposit_shares -= shares;
+        agent.credit(asset, amount);
+
+        self.agents.insert(agent_id, agent)?;
+        self.save_lending(pool, position)?;
+        Ok(amount)
+    }
+
+    /// Posts `amount` of `collateral_asset` as collateral for borrowing `asset`. A position
+    /// holds one collateral asset at a time; another asset needs price feeds for both.
+    pub async fn add_collateral(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        collateral_asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let mut pool = self.load_lending_pool(asset, now).await?;
+        let mut position = self.load_lending_position(agent_id, asset).await?;
+
+        if position.collateral == 0 {
+            position.collateral_asset = collateral_asset.to_string();
+        } else if position.collateral_asset != collateral_asset {
+            return Err(AgentChainError::LendingFailed(format!(
+                "Position already holds {} collateral",
+                position.collateral_asset
+            )));
+        }
+        if collateral_asset != asset {
+            let price_feeds = &self.config.get().lending.price_feeds;
+            if !price_feeds.contains_key(asset) || !price_feeds.contains_key(collateral_asset) {
+                return Err(AgentChainError::LendingFailed(format!(
+                    "{} cannot back loans in {} without price feeds for both",
+                    collateral_asset, asset
+                )));
+            }
+        }
+
+        agent.debit(collateral_asset, amount)?;
+        self.track_collateral(&mut pool, collateral_asset, amount, 0, now).await?;
+        position.collateral += amount;
+
+        self.agents.insert(agent_id, agent)?;
+        self.save_lending(pool, position)
+    }
+
+    pub async fn remove_collateral(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let mut pool = self.load_lending_pool(asset, now).await?;
+        let mut position = self.load_lending_position(agent_id, asset).await?;
+
+        if amount > position.collateral {
+            return Err(AgentChainError::InsufficientBalance {
+                required: amount,
+                available: position.collateral,
+            });
+        }
+        position.collateral -= amount;
+        self.check_borrow_capacity(&pool, &position, now).await?;
+        let collateral_asset = position.collateral_asset.clone();
+        self.track_collateral(&mut pool, &collateral_asset, 0, amount, now).await?;
+        agent.credit(&collateral_asset, amount);
+
+        self.agents.insert(agent_id, agent)?;
+        self.save_lending(pool, position)
+    }
+
+    /// Lends `amount` out of the pool against the agent's collateral.
+    pub async fn borrow_from_pool(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let mut pool = self.load_lending_pool(asset, now).await?;
+        let mut position = self.load_lending_position(agent_id, asset).await?;
+
+        if amount > pool.available_liquidity() {
+            return Err(AgentChainError::InsufficientBalance {
+                required: amount,
+                available: pool.available_liquidity(),
+            });
+        }
+        let scaled = mul_div_ceil(amount, INDEX_SCALE, pool.borrow_index).ok_or_else(|| {
+            AgentChainError::LendingFailed("Borrow is too large".to_string())
+        })?;
+        position.scaled_debt += scaled;
+        self.check_borrow_capacity(&pool, &position, now).await?;
+        pool.total_scaled_debt += scaled;
+        agent.credit(asset, amount);
+
+        self.agents.insert(agent_id, agent)?;
+        self.save_lending(pool, position)
+    }
+
+    /// Repays up to `amount` of the agent's debt and returns how much was actually repaid.
+    pub async fn repay_to_pool(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let mut pool = self.load_lending_pool(asset, now).await?;
+        let mut position = self.load_lending_position(agent_id, asset).await?;
+
+        let repaid = Self::reduce_debt(&mut pool, &mut position, amount);
+        agent.debit(asset, repaid)?;
+
+        self.agents.insert(agent_id, agent)?;
+        self.save_lending(pool, position)?;
+        Ok(repaid)
+    }
+
+    /// Repays part of an undercollateralized borrower's debt and seizes the matching
+    /// collateral plus the liquidation bonus. Returns the collateral seized.
+    pub async fn liquidate(
+        &mut self,
+        liquidator_agent: &str,
+        borrower_agent: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<u128, AgentChainError> {
+        let config = self.config.get().lending.clone();
+        let mut liquidator = self.get_agent(liquidator_agent).await?;
+        let mut pool = self.load_lending_pool(asset, now).await?;
+        let mut position = self.load_lending_position(borrower_agent, asset).await?;
+
+        let collateral_value = self.collateral_value(&position, now).await?;
+        if !position.is_liquidatable(&pool, &config, collateral_value) {
+            return Err(AgentChainError::LendingFailed(
+                "Position is sufficiently collateralized".to_string(),
+            ));
+        }
+
+        let repaid = Self::reduce_debt(&mut pool, &mut position, amount);
+        liquidator.debit(asset, repaid)?;
+        let seized_value = mul_div(repaid, BPS + config.liquidation_bonus_bps as u128, BPS)
+            .unwrap_or(u128::MAX);
+        let seized = position.collateral_worth(seized_value, collateral_value);
+        position.collateral -= seized;
+        let collateral_asset = position.collateral_asset.clone();
+        self.track_collateral(&mut pool, &collateral_asset, 0, seized, now).await?;
+        liquidator.credit(&collateral_asset, seized);
+
+        self.agents.insert(liquidator_agent, liquidator)?;
+        self.save_lending(pool, position)?;
+        Ok(seized)
+    }
+
+    fn reduce_debt(pool: &mut LendingPool, position: &mut LendingPosition, amount: u128) -> u128 {
+        let debt = position.debt(pool);
+        if amount >= debt {
+            pool.total_scaled_debt -= position.scaled_debt;
+            position.scaled_debt = 0;
+            debt
+        } else {
+            let scaled = mul_div(amount, INDEX_SCALE, pool.borrow_index)
+                .unwrap_or(position.scaled_debt)
+                .min(position.scaled_debt);
+            pool.total_scaled_debt -= scaled;
+            position.scaled_debt -= scaled;
+            amount
+        }
+    }
+
+    async fn check_borrow_capacity(
+        &self,
+        pool: &LendingPool,
+        position: &LendingPosition,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let debt = position.debt(pool);
+        if debt == 0 {
+            return Ok(());
+        }
+        let collateral_value = self.collateral_value(position, now).await?;
+        let collateral_factor = self.config.get().lending.collateral_factor_bps as u128;
+        let capacity = mul_div(collateral_value, collateral_factor, BPS).unwrap_or(u128::MAX);
+        if debt > capacity {
+            return Err(AgentChainError::LendingFailed(format!(
+                "Debt of {} exceeds borrowing capacity {}",
+                debt, capacity
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(deposits: u128, scaled_debt: u128) -> LendingPool {
+        LendingPool {
+            total_deposits: deposits,
+            total_shares: deposits,
+            total_scaled_debt: scaled_debt,
+            ..LendingPool::new("native", 0)
+        }
+    }
+
+    fn position(collateral: u128, scaled_debt: u128) -> LendingPosition {
+        LendingPosition {
+            agent_id: "a".to_string(),
+            asset: "native".to_string(),
+            deposit_shares: 0,
+            collateral_asset: "native".to_string(),
+            collateral,
+            scaled_debt,
+        }
+    }
+
+    #[test]
+    fn borrow_rate_grows_with_utilization() {
+        let config = LendingConfig::default();
+        assert_eq!(pool(1_000, 0).borrow_rate_bps(&config), 200);
+        assert_eq!(pool(1_000, 500).utilization_bps(), 5_000);
+        assert_eq!(pool(1_000, 500).borrow_rate_bps(&config), 1_200);
+        assert_eq!(pool(1_000, 2_000).utilization_bps(), BPS);
+    }
+
+    #[test]
+    fn a_year_of_interest_grows_the_index_and_deposits() {
+        let config = LendingConfig::default();
+        let mut pool = pool(1_000_000, 500_000);
+        pool.accrue(&config, SECONDS_PER_YEAR as u64);
+
+        // 12% a year at 50% utilization.
+        assert_eq!(pool.borrow_index, INDEX_SCALE * 112 / 100);
+        assert_eq!(pool.total_borrowed(), 560_000);
+        assert_eq!(pool.total_deposits, 1_060_000);
+        assert_eq!(pool.last_accrued_at, SECONDS_PER_YEAR as u64);
+    }
+
+    #[test]
+    fn debt_rounds_up_against_the_borrower() {
+        let mut pool = pool(1_000, 3);
+        pool.borrow_index = INDEX_SCALE + INDEX_SCALE / 3;
+        assert_eq!(position(0, 3).debt(&pool), 4);
+        assert_eq!(position(0, 1).debt(&pool), 2);
+    }
+
+    #[test]
+    fn index_math_does_not_overflow_for_large_amounts() {
+        let config = LendingConfig::default();
+        let large = u128::MAX / 4;
+        let mut pool = pool(large, large / 2);
+        pool.accrue(&config, SECONDS_PER_YEAR as u64);
+        assert!(pool.borrow_index > INDEX_SCALE);
+        assert!(pool.total_borrowed() > large / 2);
+
+        let position = position(large, large / 2);
+        assert!(position.debt(&pool) > large / 2);
+        assert!(!position.is_liquidatable(&pool, &config, large));
+    }
+
+    #[test]
+    fn positions_become_liquidatable_only_through_interest() {
+        let config = LendingConfig::default();
+        let mut pool = pool(1_000_000, 6_000);
+        // 6 000 borrowed against 10 000 of the same asset, under the 66.67% collateral
+        // factor and well under the 80% liquidation threshold.
+        let position = position(10_000, 6_000);
+        assert!(!position.is_liquidatable(&pool, &config, 10_000));
+
+        // Since collateral and debt share an asset, only accrued interest can push the
+        // debt past the threshold.
+        let years = 16;
+        pool.accrue(&config, years * SECONDS_PER_YEAR as u64);
+        assert!(position.debt(&pool) > 8_000);
+        assert!(position.is_liquidatable(&pool, &config, 10_000));
+    }
+
+    #[test]
+    fn collateral_in_another_asset_moves_with_its_price() {
+        let config = LendingConfig::default();
+        let pool = pool(1_000_000, 6_000);
+        // 10 units of collateral priced at 1 000 back 6 000 of an asset priced at 1.
+        let position = position(10, 6_000);
+        let value = convert_value(position.collateral, 1_000, 1).unwrap();
+        assert_eq!(value, 10_000);
+        assert!(!position.is_liquidatable(&pool, &config, value));
+
+        // A 30% drop in the collateral's price puts the debt past the 80% threshold.
+        let value = convert_value(position.collateral, 700, 1).unwrap();
+        assert!(position.is_liquidatable(&pool, &config, value));
+        assert_eq!(convert_value(10, 1, 0), None);
+    }
+
+    #[test]
+    fn seized_collateral_follows_the_collateral_price() {
+        let position = position(10, 6_000);
+        // With all 10 units worth 7 000, 2 100 of the borrowed asset buys 3 units.
+        assert_eq!(position.collateral_worth(2_100, 7_000), 3);
+        assert_eq!(position.collateral_worth(70_000, 7_000), 10);
+        assert_eq!(position.collateral_worth(1, 0), 10);
+    }
+}
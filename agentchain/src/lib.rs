@@ -1,7 +1,33 @@
 mod state;
+mod amm;
+mod credit;
+mod governance;
+mod insurance;
+mod jobs;
+mod lending;
+mod math;
+mod oracle;
+mod orderbook;
+mod rfq;
+mod routing;
+mod strategy_app;
+mod validation;
 mod contract;
 mod service;
 
 pub use state::*;
+pub use amm::*;
+pub use credit::*;
+pub use governance::*;
+pub use insurance::*;
+pub use jobs::*;
+pub use lending::*;
+pub use math::*;
+pub use oracle::*;
+pub use orderbook::*;
+pub use rfq::*;
+pub use routing::*;
+pub use strategy_app::*;
+pub use validation::*;
 pub use contract::*;
 pub use service::*;
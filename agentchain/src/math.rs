@@ -0,0 +1,129 @@
+/// Full 256-bit product of `a` and `b`, as `(high, low)` halves.
+fn wide_mul(a: u128, b: u128) -> (u128, u128) {
+    const LOW: u128 = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & LOW);
+    let (b_high, b_low) = (b >> 64, b & LOW);
+
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (low_high & LOW) + (high_low & LOW);
+    let low = (low_low & LOW) | ((middle & LOW) << 64);
+    let high = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    (high, low)
+}
+
+/// Computes `a * b / denominator` rounded down and its remainder, without overflowing
+/// on the intermediate product. Returns `None` if `denominator` is zero or the quotient
+/// does not fit in a `u128`.
+fn mul_div_rem(a: u128, b: u128, denominator: u128) -> Option<(u128, u128)> {
+    if denominator == 0 {
+        return None;
+    }
+    let (high, low) = wide_mul(a, b);
+    if high == 0 {
+        return Some((low / denominator, low % denominator));
+    }
+    if high >= denominator {
+        return None;
+    }
+
+    let mut quotient = 0u128;
+    let mut remainder = high;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+    Some((quotient, remainder))
+}
+
+/// `a * b / denominator`, rounded down.
+pub fn mul_div(a: u128, b: u128, denominator: u128) -> Option<u128> {
+    mul_div_rem(a, b, denominator).map(|(quotient, _)| quotient)
+}
+
+/// `a * b / denominator`, rounded up.
+pub fn mul_div_ceil(a: u128, b: u128, denominator: u128) -> Option<u128> {
+    let (quotient, remainder) = mul_div_rem(a, b, denominator)?;
+    if remainder == 0 {
+        Some(quotient)
+    } else {
+        quotient.checked_add(1)
+    }
+}
+
+/// Square root of `a * b`, rounded down. The product may exceed a `u128`, but its
+/// root always fits.
+pub fn sqrt_mul(a: u128, b: u128) -> u128 {
+    let (high, low) = wide_mul(a, b);
+    if high == 0 {
+        return low.isqrt();
+    }
+    // Newton's method, which decreases monotonically from any start above the root
+    // and stops at the rounded-down root.
+    let mut root = u128::MAX;
+    loop {
+        // Only fails once `root` is already the root of a product close to 2^256.
+        let Some(quotient) = mul_div(a, b, root) else {
+            return root;
+        };
+        let next = (root >> 1) + (quotient >> 1) + (root & quotient & 1);
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_products_divide_exactly() {
+        assert_eq!(mul_div(6, 7, 3), Some(14));
+        assert_eq!(mul_div(10, 1, 3), Some(3));
+        assert_eq!(mul_div_ceil(10, 1, 3), Some(4));
+        assert_eq!(mul_div_ceil(9, 1, 3), Some(3));
+    }
+
+    #[test]
+    fn products_wider_than_u128_are_divided_without_overflow() {
+        let scale = 1_000_000_000_000_000_000u128;
+        assert_eq!(mul_div(u128::MAX, scale, scale), Some(u128::MAX));
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(mul_div(u128::MAX, 2, 4), Some(u128::MAX / 2));
+        assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 90), Some(1 << 110));
+        assert_eq!(mul_div_ceil(u128::MAX, 3, 6), Some(u128::MAX / 2 + 1));
+    }
+
+    #[test]
+    fn overflowing_quotients_and_zero_denominators_fail() {
+        assert_eq!(mul_div(u128::MAX, 2, 1), None);
+        assert_eq!(mul_div(1, 1, 0), None);
+        assert_eq!(mul_div_ceil(u128::MAX, u128::MAX, u128::MAX - 1), None);
+    }
+
+    #[test]
+    fn square_roots_of_wide_products_round_down() {
+        assert_eq!(sqrt_mul(0, 5), 0);
+        assert_eq!(sqrt_mul(4, 9), 6);
+        assert_eq!(sqrt_mul(2, 4), 2);
+        let seed = 1_000_000_000_000_000_000_000_000u128;
+        assert_eq!(sqrt_mul(seed, seed), seed);
+        assert_eq!(sqrt_mul(seed, 4 * seed), 2 * seed);
+        assert_eq!(sqrt_mul(u128::MAX, u128::MAX), u128::MAX);
+        assert_eq!(sqrt_mul(u128::MAX, 1), u128::MAX.isqrt());
+        assert_eq!(sqrt_mul(1 << 127, 2), 1 << 64);
+        let root = sqrt_mul(u128::MAX, 3);
+        assert!(wide_mul(root, root) <= wide_mul(u128::MAX, 3));
+        assert!(wide_mul(root + 1, root + 1) > wide_mul(u128::MAX, 3));
+    }
+}
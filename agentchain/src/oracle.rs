@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::math::mul_div;
+use crate::state::{AgentChainError, AgentChainState, AgentStrategy};
+
+/// Number of aggregated values kept per feed.
+pub const MAX_FEED_HISTORY: usize = 100;
+
+const BPS: u128 = 10_000;
+
+/// How submissions are turned into a feed value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OracleConfig {
+    /// Fresh submissions needed before a feed value is published.
+    pub min_sources: u32,
+    /// Distance from the median, relative to the median, beyond which a submission
+    /// counts as an outlier.
+    pub outlier_deviation_bps: u32,
+    /// Reputation an Oracle agent loses for each outlying submission.
+    pub outlier_penalty: u64,
+}
+
+impl Default for OracleConfig {
+    fn default() -> Self {
+        OracleConfig {
+            min_sources: 3,
+            outlier_deviation_bps: 500,
+            outlier_penalty: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedSubmission {
+    pub value: u128,
+    pub submitted_at: u64,
+    /// Seconds after which the submission is stale, from the agent's `update_frequency`.
+    pub valid_for: u64,
+    /// Whether the submission has already been checked against a median.
+    pub evaluated: bool,
+}
+
+impl FeedSubmission {
+    pub fn is_fresh(&self, now: u64) -> bool {
+        now.saturating_sub(self.submitted_at) <= self.valid_for
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedValue {
+    /// Median of the fresh submissions.
+    pub value: u128,
+    pub sources: u32,
+    pub aggregated_at: u64,
+}
+
+/// A named data feed fed by Oracle agents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataFeed {
+    pub id: String,
+    /// Latest submission per Oracle agent.
+    pub submissions: BTreeMap<String, FeedSubmission>,
+    pub latest: Option<FeedValue>,
+    /// Past aggregated values, oldest first, at most `MAX_FEED_HISTORY`.
+    pub history: Vec<FeedValue>,
+}
+
+impl DataFeed {
+    fn new(id: &str) -> Self {
+        DataFeed {
+            id: id.to_string(),
+            submissions: BTreeMap::new(),
+            latest: None,
+            history: Vec::new(),
+        }
+    }
+}
+
+fn median(values: &mut [u128]) -> u128 {
+    values.sort_unstable();
+    let middle = values.len() / 2;
+    if values.len() % 2 == 0 {
+        values[middle - 1].midpoint(values[middle])
+    } else {
+        values[middle]
+    }
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    pub async fn get_data_feed(&self, feed_id: &str) -> Result<DataFeed, AgentChainError> {
+        self.data_feeds
+            .get(feed_id)
+            .await?
+            .ok_or_else(|| AgentChainError::OracleFailed(format!("Feed {} not found", feed_id)))
+    }
+
+    /// Latest aggregated value of a feed.
+    pub async fn latest_feed_value(&self, feed_id: &str) -> Result<FeedValue, AgentChainError> {
+        self.get_data_feed(feed_id).await?.latest.ok_or_else(|| {
+            AgentChainError::OracleFailed(format!("Feed {} has no value yet", feed_id))
+        })
+    }
+
+    /// Records an Oracle agent's value for `feed_id` and re-aggregates the feed. Returns
+    /// the new feed value, if enough fresh submissions exist.
+    ///
+    /// A submission stays fresh for the agent's `update_frequency`, and an agent may not
+    /// submit to the same feed more than once per `update_frequency`.
+    pub async fn submit_feed_value(
+        &mut self,
+        agent_id: &str,
+        feed_id: &str,
+        value: u128,
+        now: u64,
+    ) -> Result<Option<FeedValue>, AgentChainError> {
+        let agent = self.get_agent(agent_id).await?;
+        let Some(AgentStrategy::Oracle { update_frequency, .. }) = agent
+            .enabled_strategy("Oracle")
+            .map(|slot| &slot.strategy)
+        else {
+            return Err(AgentChainError::OracleFailed(
+                "Only agents running an Oracle strategy can submit feed values".to_string(),
+            ));
+        };
+        if !agent.is_active {
+            return Err(AgentChainError::OracleFailed("Agent is inactive".to_string()));
+        }
+
+        let mut feed = self
+            .data_feeds
+            .get(feed_id)
+            .await?
+            .unwrap_or_else(|| DataFeed::new(feed_id));
+        if let Some(previous) = feed.submissions.get(agent_id) {
+            let next_allowed_at = previous.submitted_at.saturating_add(*update_frequency);
+            if now < next_allowed_at {
+                return Err(AgentChainError::OracleFailed(format!(
+                    "Next submission allowed at {}",
+                    next_allowed_at
+                )));
+            }
+        }
+        feed.submissions.insert(
+            agent_id.to_string(),
+            FeedSubmission {
+                value,
+                submitted_at: now,
+                valid_for: *update_frequency,
+                evaluated: false,
+            },
+        );
+
+        let aggregated = self.aggregate_feed(&mut feed, now).await?;
+        self.data_feeds.insert(feed_id, feed)?;
+        Ok(aggregated)
+    }
+
+    /// Publishes the median of the fresh submissions once there are enough of them, and
+    /// penalizes the agents whose new submissions lie too far from it.
+    async fn aggregate_feed(
+        &mut self,
+        feed: &mut DataFeed,
+        now: u64,
+    ) -> Result<Option<FeedValue>, AgentChainError> {
+        let config = self.config.get().oracle.clone();
+        let mut values = feed
+            .submissions
+            .values()
+            .filter(|submission| submission.is_fresh(now))
+            .map(|submission| submission.value)
+            .collect::<Vec<_>>();
+        if values.is_empty() || (values.len() as u32) < config.min_sources {
+            return Ok(None);
+        }
+        let value = median(&mut values);
+        let max_deviation = mul_div(value, config.outlier_deviation_bps as u128, BPS)
+            .ok_or_else(|| AgentChainError::OracleFailed("Deviation overflows".to_string()))?;
+
+        for (agent_id, submission) in feed.submissions.iter_mut() {
+            if submission.evaluated || !submission.is_fresh(now) {
+                continue;
+            }
+            submission.evaluated = true;
+            if submission.value.abs_diff(value) > max_deviation {
+                let mut agent = self.get_agent(agent_id).await?;
+                agent.reputation = agent.reputation.saturating_sub(config.outlier_penalty);
+                self.agents.insert(agent_id, agent)?;
+            }
+        }
+
+        let feed_value = FeedValue {
+            value,
+            sources: values.len() as u32,
+            aggregated_at: now,
+        };
+        if let Some(previous) = feed.latest.replace(feed_value.clone()) {
+            feed.history.push(previous);
+            if feed.history.len() > MAX_FEED_HISTORY {
+                feed.history.remove(0);
+            }
+        }
+        Ok(Some(feed_value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_of_an_odd_count_is_the_middle_value() {
+        assert_eq!(median(&mut [7]), 7);
+        assert_eq!(median(&mut [30, 10, 20]), 20);
+        assert_eq!(median(&mut [5, 1_000_000, 4, 6, 0]), 5);
+    }
+
+    #[test]
+    fn median_of_an_even_count_is_the_midpoint_of_the_middle_pair() {
+        assert_eq!(median(&mut [10, 20]), 15);
+        assert_eq!(median(&mut [40, 10, 30, 20]), 25);
+        assert_eq!(median(&mut [u128::MAX, u128::MAX - 2]), u128::MAX - 1);
+    }
+
+    #[test]
+    fn submissions_stay_fresh_for_their_validity_window() {
+        let submission = FeedSubmission {
+            value: 1,
+            submitted_at: 1_000,
+            valid_for: 60,
+            evaluated: false,
+        };
+        assert!(submission.is_fresh(1_000));
+        assert!(submission.is_fresh(1_060));
+        assert!(!submission.is_fresh(1_061));
+        assert!(submission.is_fresh(0));
+    }
+}
@@ -0,0 +1,451 @@
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::math::mul_div;
+use crate::state::{AgentChainError, AgentChainState, AgentStrategy, TransactionType};
+
+/// Share of the capital allocated to a Trading strategy that may be committed to open
+/// orders per point of `risk_level`. At the default maximum risk level of 10 the whole
+/// allocation can be committed.
+pub const EXPOSURE_BPS_PER_RISK_LEVEL: u128 = 1_000;
+
+const BPS: u128 = 10_000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum OrderSide {
+    /// Buys `base_asset`, paying in `quote_asset`.
+    Buy,
+    /// Sells `base_asset` for `quote_asset`.
+    Sell,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum OrderStatus {
+    Open,
+    Filled,
+    Cancelled,
+}
+
+/// A limit order from a Trading agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub id: String,
+    pub agent_id: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub side: OrderSide,
+    /// Units of `quote_asset` per unit of `base_asset`.
+    pub price: u128,
+    /// Units of `base_asset` to trade.
+    pub amount: u128,
+    pub filled: u128,
+    pub status: OrderStatus,
+    pub created_at: u64,
+}
+
+impl Order {
+    pub fn remaining(&self) -> u128 {
+        self.amount - self.filled
+    }
+
+    /// Asset the order pays out when it fills.
+    pub fn committed_asset(&self) -> &str {
+        match self.side {
+            OrderSide::Buy => &self.quote_asset,
+            OrderSide::Sell => &self.base_asset,
+        }
+    }
+
+    /// Amount of `committed_asset` still needed to fill the rest of the order, or `None`
+    /// if it does not fit in a `u128`.
+    pub fn committed_amount(&self) -> Option<u128> {
+        match self.side {
+            OrderSide::Buy => self.remaining().checked_mul(self.price),
+            OrderSide::Sell => Some(self.remaining()),
+        }
+    }
+}
+
+/// Resting order ids for one asset pair, best price first and oldest first at equal
+/// prices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderBook {
+    pub base_asset: String,
+    pub quote_asset: String,
+    /// Buy orders by descending price.
+    pub bids: Vec<String>,
+    /// Sell orders by ascending price.
+    pub asks: Vec<String>,
+}
+
+impl OrderBook {
+    fn new(base_asset: &str, quote_asset: &str) -> Self {
+        OrderBook {
+            base_asset: base_asset.to_string(),
+            quote_asset: quote_asset.to_string(),
+            bids: Vec::new(),
+            asks: Vec::new(),
+        }
+    }
+
+    fn side_mut(&mut self, side: OrderSide) -> &mut Vec<String> {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        }
+    }
+
+    fn remove(&mut self, order_id: &str) {
+        self.bids.retain(|id| id != order_id);
+        self.asks.retain(|id| id != order_id);
+    }
+}
+
+/// A trade between a resting order and an incoming one, at the resting order's price.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fill {
+    pub maker_order: String,
+    pub taker_order: String,
+    pub price: u128,
+    pub amount: u128,
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    pub fn order_book_key(base_asset: &str, quote_asset: &str) -> String {
+        format!("{}/{}", base_asset, quote_asset)
+    }
+
+    pub async fn get_order(&self, order_id: &str) -> Result<Order, AgentChainError> {
+        self.orders
+            .get(order_id)
+            .await?
+            .ok_or_else(|| AgentChainError::OrderFailed("Order not found".to_string()))
+    }
+
+    pub async fn get_order_book(
+        &self,
+        base_asset: &str,
+        quote_asset: &str,
+    ) -> Result<OrderBook, AgentChainError> {
+        Ok(self
+            .order_books
+            .get(&Self::order_book_key(base_asset, quote_asset))
+            .await?
+            .unwrap_or_else(|| OrderBook::new(base_asset, quote_asset)))
+    }
+
+    pub fn order_exposure_key(agent_id: &str, asset: &str) -> String {
+        format!("{}_{}", agent_id, asset)
+    }
+
+    /// Amount of `asset` committed to `agent_id`'s open orders across all books.
+    pub async fn open_exposure(&self, agent_id: &str, asset: &str) -> Result<u128, AgentChainError> {
+        Ok(self
+            .order_exposure
+            .get(&Self::order_exposure_key(agent_id, asset))
+            .await?
+            .unwrap_or(0))
+    }
+
+    async fn add_exposure(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+    ) -> Result<(), AgentChainError> {
+        let exposure = self
+            .open_exposure(agent_id, asset)
+            .await?
+            .checked_add(amount)
+            .ok_or_else(|| AgentChainError::OrderFailed("Open exposure overflows".to_string()))?;
+        self.order_exposure
+            .insert(&Self::order_exposure_key(agent_id, asset), exposure)?;
+        Ok(())
+    }
+
+    async fn release_exposure(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+    ) -> Result<(), AgentChainError> {
+        let key = Self::order_exposure_key(agent_id, asset);
+        let exposure = self.open_exposure(agent_id, asset).await?.saturating_sub(amount);
+        if exposure == 0 {
+            self.order_exposure.remove(&key)?;
+        } else {
+            self.order_exposure.insert(&key, exposure)?;
+        }
+        Ok(())
+    }
+
+    /// Places a limit order for a Trading agent and matches it against the opposite side
+    /// of the book with price-time priority. Whatever does not fill rests on the book.
+    ///
+    /// Open orders may commit at most `risk_level * EXPOSURE_BPS_PER_RISK_LEVEL` of the
+    /// share of the agent's balance allocated to its Trading strategy, in the asset they
+    /// pay out.
+    pub async fn place_order(
+        &mut self,
+        agent_id: &str,
+        base_asset: String,
+        quote_asset: String,
+        side: OrderSide,
+        price: u128,
+        amount: u128,
+        now: u64,
+    ) -> Result<(String, Vec<Fill>), AgentChainError> {
+        if base_asset == quote_asset {
+            return Err(AgentChainError::OrderFailed(
+                "Base and quote assets must differ".to_string(),
+            ));
+        }
+        if price == 0 || amount == 0 {
+            return Err(AgentChainError::OrderFailed(
+                "Price and amount must be positive".to_string(),
+            ));
+        }
+        let agent = self.get_agent(agent_id).await?;
+        let trading = agent
+            .enabled_strategy("Trading")
+            .and_then(|slot| match slot.strategy {
+                AgentStrategy::Trading { risk_level, .. } => Some((risk_level, slot.allocation_bps)),
+                _ => None,
+            });
+        let Some((risk_level, allocation_bps)) = trading else {
+            return Err(AgentChainError::OrderFailed(
+                "Only agents running a Trading strategy can place orders".to_string(),
+            ));
+        };
+
+        let mut total_orders = *self.total_orders.get();
+        let mut order = Order {
+            id: format!("order_{}_{}", now, total_orders),
+            agent_id: agent_id.to_string(),
+            base_asset,
+            quote_asset,
+            side,
+            price,
+            amount,
+            filled: 0,
+            status: OrderStatus::Open,
+            created_at: now,
+        };
+        total_orders += 1;
+        self.total_orders.set(total_orders);
+
+        let asset = order.committed_asset().to_string();
+        let overflow = || AgentChainError::OrderFailed("Order value overflows".to_string());
+        let allocated =
+            mul_div(agent.balance_of(&asset), allocation_bps as u128, BPS).ok_or_else(overflow)?;
+        let max_exposure = mul_div(
+            allocated,
+            risk_level as u128 * EXPOSURE_BPS_PER_RISK_LEVEL,
+            BPS,
+        )
+        .ok_or_else(overflow)?;
+        let open_exposure = self.open_exposure(agent_id, &asset).await?;
+        let exposure = order
+            .committed_amount()
+            .and_then(|committed| committed.checked_add(open_exposure))
+            .ok_or_else(overflow)?;
+        if exposure > max_exposure {
+            return Err(AgentChainError::OrderFailed(format!(
+                "Open exposure of {} {} would exceed the limit of {} for risk level {}",
+                exposure, asset, max_exposure, risk_level
+            )));
+        }
+
+        let mut book = self.get_order_book(&order.base_asset, &order.quote_asset).await?;
+        let fills = self.match_order(&mut book, &mut order, now).await?;
+
+        if order.remaining() == 0 {
+            order.status = OrderStatus::Filled;
+        } else {
+            self.rest_order(&mut book, &order).await?;
+        }
+        let order_id = order.id.clone();
+        self.orders.insert(&order_id, order)?;
+        self.order_books.insert(
+            &Self::order_book_key(&book.base_asset, &book.quote_asset),
+            book,
+        )?;
+
+        Ok((order_id, fills))
+    }
+
+    async fn match_order(
+        &mut self,
+        book: &mut OrderBook,
+        taker: &mut Order,
+        now: u64,
+    ) -> Result<Vec<Fill>, AgentChainError> {
+        let opposite = match taker.side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        let resting = book.side_mut(opposite).clone();
+        let mut fills = Vec::new();
+        let mut closed = Vec::new();
+
+        for maker_id in resting {
+            if taker.remaining() == 0 {
+                break;
+            }
+            let mut maker = self.get_order(&maker_id).await?;
+            let crosses = match taker.side {
+                OrderSide::Buy => maker.price <= taker.price,
+                OrderSide::Sell => maker.price >= taker.price,
+            };
+            if !crosses {
+                break;
+            }
+            if maker.agent_id == taker.agent_id {
+                continue;
+            }
+
+            let amount = maker.remaining().min(taker.remaining());
+            let cost = amount
+                .checked_mul(maker.price)
+                .ok_or_else(|| AgentChainError::OrderFailed("Fill cost overflows".to_string()))?;
+
+            // A maker whose balance or spending policy no longer covers its side of the
+            // fill is dropped from the book, so it cannot block the takers behind it.
+            let maker_balance = self.get_agent(&maker.agent_id).await?.balance_of(maker.committed_asset());
+            let maker_owes = match maker.side {
+                OrderSide::Buy => cost,
+                OrderSide::Sell => amount,
+            };
+            let maker_can_pay = maker_balance >= maker_owes
+                && self
+                    .check_spending(&maker.agent_id, &taker.agent_id, maker.committed_asset(), maker_owes, now)
+                    .await
+                    .is_ok();
+            if !maker_can_pay {
+                let committed = maker.committed_amount().unwrap_or(u128::MAX);
+                self.release_exposure(&maker.agent_id, maker.committed_asset(), committed)
+                    .await?;
+                maker.status = OrderStatus::Cancelled;
+                self.orders.insert(&maker_id, maker)?;
+                closed.push(maker_id);
+                continue;
+            }
+
+            let (buyer, seller) = match taker.side {
+                OrderSide::Buy => (taker.agent_id.clone(), maker.agent_id.clone()),
+                OrderSide::Sell => (maker.agent_id.clone(), taker.agent_id.clone()),
+            };
+            self.transfer_tokens(&seller, &buyer, &taker.base_asset, amount, TransactionType::OrderFill, now)
+                .await?;
+            self.transfer_tokens(&buyer, &seller, &taker.quote_asset, cost, TransactionType::OrderFill, now)
+                .await?;
+
+            self.release_exposure(&maker.agent_id, maker.committed_asset(), maker_owes)
+                .await?;
+            maker.filled += amount;
+            taker.filled += amount;
+            fills.push(Fill {
+                maker_order: maker_id.clone(),
+                taker_order: taker.id.clone(),
+                price: maker.price,
+                amount,
+            });
+            if maker.remaining() == 0 {
+                maker.status = OrderStatus::Filled;
+                closed.push(maker_id.clone());
+            }
+            self.orders.insert(&maker_id, maker)?;
+        }
+
+        for order_id in &closed {
+            book.remove(order_id);
+        }
+        Ok(fills)
+    }
+
+    /// Inserts `order` behind every resting order at the same or a better price, and
+    /// counts what it still commits towards its agent's open exposure.
+    async fn rest_order(&mut self, book: &mut OrderBook, order: &Order) -> Result<(), AgentChainError> {
+        let side = book.side_mut(order.side).clone();
+        let mut position = side.len();
+        for (index, order_id) in side.iter().enumerate() {
+            let resting = self.get_order(order_id).await?;
+            let worse = match order.side {
+                OrderSide::Buy => resting.price < order.price,
+                OrderSide::Sell => resting.price > order.price,
+            };
+            if worse {
+                position = index;
+                break;
+            }
+        }
+        book.side_mut(order.side).insert(position, order.id.clone());
+        let committed = order
+            .committed_amount()
+            .ok_or_else(|| AgentChainError::OrderFailed("Order value overflows".to_string()))?;
+        self.add_exposure(&order.agent_id, order.committed_asset(), committed)
+            .await
+    }
+
+    pub async fn cancel_order(&mut self, order_id: &str) -> Result<(), AgentChainError> {
+        let mut order = self.get_order(order_id).await?;
+        if order.status != OrderStatus::Open {
+            return Err(AgentChainError::OrderFailed("Order is not open".to_string()));
+        }
+        let mut book = self.get_order_book(&order.base_asset, &order.quote_asset).await?;
+        book.remove(order_id);
+        self.order_books.insert(
+            &Self::order_book_key(&order.base_asset, &order.quote_asset),
+            book,
+        )?;
+
+        let committed = order.committed_amount().unwrap_or(u128::MAX);
+        self.release_exposure(&order.agent_id, order.committed_asset(), committed)
+            .await?;
+        order.status = OrderStatus::Cancelled;
+        self.orders.insert(order_id, order)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(side: OrderSide, price: u128, amount: u128) -> Order {
+        Order {
+            id: "order_1".to_string(),
+            agent_id: "trader".to_string(),
+            base_asset: "eth".to_string(),
+            quote_asset: "usdc".to_string(),
+            side,
+            price,
+            amount,
+            filled: 0,
+            status: OrderStatus::Open,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn orders_commit_what_they_pay_out_for_the_unfilled_rest() {
+        let mut buy = order(OrderSide::Buy, 3, 10);
+        buy.filled = 4;
+        assert_eq!(buy.committed_asset(), "usdc");
+        assert_eq!(buy.committed_amount(), Some(18));
+
+        let mut sell = order(OrderSide::Sell, 3, 10);
+        sell.filled = 4;
+        assert_eq!(sell.committed_asset(), "eth");
+        assert_eq!(sell.committed_amount(), Some(6));
+    }
+
+    #[test]
+    fn committed_amounts_that_overflow_are_rejected() {
+        assert_eq!(order(OrderSide::Buy, u128::MAX, 2).committed_amount(), None);
+        assert_eq!(
+            order(OrderSide::Sell, u128::MAX, 2).committed_amount(),
+            Some(2)
+        );
+    }
+}
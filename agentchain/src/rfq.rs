@@ -0,0 +1,288 @@
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::math::mul_div;
+use crate::state::{Agent, AgentChainError, AgentChainState, ServiceTerms};
+
+/// Weight of the price discount in the auto-award score.
+pub const RFQ_PRICE_WEIGHT_BPS: u128 = 6_000;
+/// Weight of the provider's reputation in the auto-award score.
+pub const RFQ_REPUTATION_WEIGHT_BPS: u128 = 4_000;
+
+const BPS: u128 = 10_000;
+
+/// A job posted for providers to bid on, awarded as a regular service request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rfq {
+    pub id: String,
+    pub requester_agent: String,
+    pub service_type: String,
+    pub parameters: String,
+    pub asset: String,
+    pub max_budget: u128,
+    pub bidding_deadline: u64,
+    pub insured: bool,
+    pub status: RfqStatus,
+    pub bids: Vec<Bid>,
+    pub awarded_provider: Option<String>,
+    pub request_id: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RfqStatus {
+    Open,
+    Awarded,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bid {
+    pub provider_agent: String,
+    pub price: u128,
+    pub submitted_at: u64,
+}
+
+impl Rfq {
+    /// Auto-award score of a bid, in basis points: cheaper bids and better reputations
+    /// score higher.
+    pub fn score_bps(&self, bid: &Bid, provider: &Agent) -> u128 {
+        let price_score = if self.max_budget == 0 {
+            0
+        } else {
+            mul_div(self.max_budget.saturating_sub(bid.price), BPS, self.max_budget).unwrap_or(0)
+        };
+        let reputation_score = (provider.reputation as u128).min(1000) * BPS / 1000;
+        (price_score * RFQ_PRICE_WEIGHT_BPS + reputation_score * RFQ_REPUTATION_WEIGHT_BPS) / BPS
+    }
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn post_rfq(
+        &mut self,
+        requester_agent: &str,
+        service_type: String,
+        parameters: String,
+        asset: String,
+        max_budget: u128,
+        bidding_deadline: u64,
+        insured: bool,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        self.get_agent(requester_agent).await?;
+        if bidding_deadline <= now {
+            return Err(AgentChainError::RfqFailed(
+                "Bidding deadline must be in the future".to_string(),
+            ));
+        }
+
+        let mut total_rfqs = *self.total_rfqs.get();
+        let rfq_id = format!("rfq_{}_{}", now, total_rfqs);
+        let rfq = Rfq {
+            id: rfq_id.clone(),
+            requester_agent: requester_agent.to_string(),
+            service_type,
+            parameters,
+            asset,
+            max_budget,
+            bidding_deadline,
+            insured,
+            status: RfqStatus::Open,
+            bids: Vec::new(),
+            awarded_provider: None,
+            request_id: None,
+            created_at: now,
+        };
+        self.rfqs.insert(&rfq_id, rfq)?;
+
+        total_rfqs += 1;
+        self.total_rfqs.set(total_rfqs);
+
+        Ok(rfq_id)
+    }
+
+    pub async fn get_rfq(&self, rfq_id: &str) -> Result<Rfq, AgentChainError> {
+        self.rfqs
+            .get(rfq_id)
+            .await?
+            .ok_or_else(|| AgentChainError::RfqFailed("RFQ not found".to_string()))
+    }
+
+    /// Places or replaces a provider's bid on an open RFQ.
+    pub async fn submit_bid(
+        &mut self,
+        rfq_id: &str,
+        provider_agent: &str,
+        price: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let mut rfq = self.get_rfq(rfq_id).await?;
+        let provider = self.get_agent(provider_agent).await?;
+
+        if rfq.status != RfqStatus::Open || now >= rfq.bidding_deadline {
+            return Err(AgentChainError::RfqFailed("Bidding is closed".to_string()));
+        }
+        if !provider.is_active || provider_agent == rfq.requester_agent {
+            return Err(AgentChainError::RfqFailed(
+                "Provider is not eligible to bid".to_string(),
+            ));
+        }
+        if price > rfq.max_budget {
+            return Err(AgentChainError::RfqFailed(format!(
+                "Bid of {} exceeds maximum budget {}",
+                price, rfq.max_budget
+            )));
+        }
+
+        rfq.bids.retain(|bid| bid.provider_agent != provider_agent);
+        rfq.bids.push(Bid {
+            provider_agent: provider_agent.to_string(),
+            price,
+            submitted_at: now,
+        });
+        self.rfqs.insert(rfq_id, rfq)?;
+        Ok(())
+    }
+
+    /// Awards the RFQ to `provider_agent`'s bid and opens an escrowed service request
+    /// at the bid price.
+    pub async fn award_rfq(
+        &mut self,
+        rfq_id: &str,
+        provider_agent: &str,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let mut rfq = self.get_rfq(rfq_id).await?;
+        if rfq.status != RfqStatus::Open {
+            return Err(AgentChainError::RfqFailed("RFQ is not open".to_string()));
+        }
+        let bid = rfq
+            .bids
+            .iter()
+            .find(|bid| bid.provider_agent == provider_agent)
+            .cloned()
+            .ok_or_else(|| AgentChainError::RfqFailed("No bid from this provider".to_string()))?;
+
+        let terms = ServiceTerms {
+            service_type: rfq.service_type.clone(),
+            parameters: rfq.parameters.clone(),
+            asset: rfq.asset.clone(),
+            payment: bid.price,
+            milestones: Vec::new(),
+            insured: rfq.insured,
+        };
+        let request_id = self
+            .create_service_request(rfq.requester_agent.clone(), bid.provider_agent.clone(), terms, now)
+            .await?;
+
+        rfq.status = RfqStatus::Awarded;
+        rfq.awarded_provider = Some(bid.provider_agent);
+        rfq.request_id = Some(request_id.clone());
+        self.rfqs.insert(rfq_id, rfq)?;
+
+        Ok(request_id)
+    }
+
+    /// Once bidding has closed, awards the RFQ to the bid with the best price and
+    /// reputation score. Returns the winning provider and the new request id.
+    pub async fn auto_award_rfq(&mut self, rfq_id: &str, now: u64) -> Result<(String, String), AgentChainError> {
+        let rfq = self.get_rfq(rfq_id).await?;
+        if now < rfq.bidding_deadline {
+            return Err(AgentChainError::RfqFailed(format!(
+                "Bidding is open until {}",
+                rfq.bidding_deadline
+            )));
+        }
+
+        let mut best: Option<(u128, &Bid)> = None;
+        for bid in &rfq.bids {
+            let provider = self.get_agent(&bid.provider_agent).await?;
+            if !provider.is_active {
+                continue;
+            }
+            let score = rfq.score_bps(bid, &provider);
+            // Ties go to the earlier bid.
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, bid));
+            }
+        }
+        let winner = best
+            .map(|(_, bid)| bid.provider_agent.clone())
+            .ok_or_else(|| AgentChainError::RfqFailed("No eligible bids".to_string()))?;
+
+        let request_id = self.award_rfq(rfq_id, &winner, now).await?;
+        Ok((winner, request_id))
+    }
+
+    pub async fn cancel_rfq(&mut self, rfq_id: &str) -> Result<(), AgentChainError> {
+        let mut rfq = self.get_rfq(rfq_id).await?;
+        if rfq.status != RfqStatus::Open {
+            return Err(AgentChainError::RfqFailed("RFQ is not open".to_string()));
+        }
+        rfq.status = RfqStatus::Cancelled;
+        self.rfqs.insert(rfq_id, rfq)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rfq(max_budget: u128) -> Rfq {
+        Rfq {
+            id: "rfq".to_string(),
+            requester_agent: "requester".to_string(),
+            service_type: "audit".to_string(),
+            parameters: String::new(),
+            asset: "native".to_string(),
+            max_budget,
+            bidding_deadline: 100,
+            insured: false,
+            status: RfqStatus::Open,
+            bids: Vec::new(),
+            awarded_provider: None,
+            request_id: None,
+            created_at: 0,
+        }
+    }
+
+    fn bid(price: u128) -> Bid {
+        Bid {
+            provider_agent: "provider".to_string(),
+            price,
+            submitted_at: 0,
+        }
+    }
+
+    fn provider(reputation: u64) -> Agent {
+        Agent {
+            reputation,
+            ..crate::state::tests::agent("provider")
+        }
+    }
+
+    #[test]
+    fn scores_weigh_the_discount_and_reputation() {
+        let rfq = rfq(1_000);
+        // Half price with no reputation scores 60% of 50%.
+        assert_eq!(rfq.score_bps(&bid(500), &provider(0)), 3_000);
+        // Full price with the maximum reputation scores 40%.
+        assert_eq!(rfq.score_bps(&bid(1_000), &provider(1_000)), 4_000);
+        assert_eq!(rfq.score_bps(&bid(0), &provider(5_000)), BPS);
+    }
+
+    #[test]
+    fn cheaper_bids_win_at_equal_reputation() {
+        let rfq = rfq(1_000);
+        let provider = provider(500);
+        assert!(rfq.score_bps(&bid(700), &provider) > rfq.score_bps(&bid(800), &provider));
+    }
+
+    #[test]
+    fn scores_handle_empty_and_huge_budgets() {
+        assert_eq!(rfq(0).score_bps(&bid(0), &provider(1_000)), 4_000);
+        assert_eq!(rfq(u128::MAX).score_bps(&bid(u128::MAX / 2), &provider(0)), 3_000);
+    }
+}
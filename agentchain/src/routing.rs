@@ -0,0 +1,248 @@
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Agent, AgentChainError, AgentChainState, MarketListing};
+
+/// Relative importance of each criterion when picking a provider from the market.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoringWeights {
+    pub price: u16,
+    pub reputation: u16,
+    pub success_rate: u16,
+}
+
+impl Default for ScoringWeights {
+    fn default() -> Self {
+        ScoringWeights {
+            price: 1,
+            reputation: 1,
+            success_rate: 1,
+        }
+    }
+}
+
+impl ScoringWeights {
+    /// Weighted average of the listing's price discount against `max_price`, the
+    /// provider's reputation and the listing's success rate, each normalized to `0..=1`.
+    pub fn score(&self, listing: &MarketListing, provider: &Agent, max_price: u128) -> f64 {
+        let total = self.price as f64 + self.reputation as f64 + self.success_rate as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+
+        let price_score = if max_price == 0 {
+            1.0
+        } else {
+            max_price.saturating_sub(listing.price) as f64 / max_price as f64
+        };
+        let reputation_score = provider.reputation.min(1000) as f64 / 1000.0;
+        let success_score = (listing.success_rate / 100.0).clamp(0.0, 1.0);
+
+        (self.price as f64 * price_score
+            + self.reputation as f64 * reputation_score
+            + self.success_rate as f64 * success_score)
+            / total
+    }
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    pub fn listing_id(agent_id: &str, service_type: &str) -> String {
+        format!("{}_{}", agent_id, service_type)
+    }
+
+    /// Number of open requests `provider_agent` is handling for `service_type`.
+    pub async fn in_flight_requests(
+        &self,
+        provider_agent: &str,
+        service_type: &str,
+    ) -> Result<u32, AgentChainError> {
+        let listing_id = Self::listing_id(provider_agent, service_type);
+        Ok(self.in_flight.get(&listing_id).await?.unwrap_or(0))
+    }
+
+    /// Checks that a provider takes new requests for `service_type`: it must be active
+    /// and, if it has a listing for the service type, the listing must be available.
+    pub(crate) async fn check_provider_available(
+        &self,
+        provider_agent: &str,
+        service_type: &str,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let provider = self.get_agent(provider_agent).await?;
+        if !provider.is_active {
+            return Err(AgentChainError::ServiceRequestFailed(format!(
+                "Provider {} is not active",
+                provider_agent
+            )));
+        }
+        let listing_id = Self::listing_id(provider_agent, service_type);
+        if let Some(listing) = self.market_listings.get(&listing_id).await? {
+            if !listing.is_available(now) {
+                return Err(AgentChainError::ServiceRequestFailed(format!(
+                    "Listing {} is not available",
+                    listing_id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes one slot of the provider's capacity for `service_type`. Providers without
+    /// a listing for the service type are not limited.
+    pub(crate) async fn reserve_capacity(
+        &mut self,
+        provider_agent: &str,
+        service_type: &str,
+    ) -> Result<(), AgentChainError> {
+        let listing_id = Self::listing_id(provider_agent, service_type);
+        let in_flight = self.in_flight.get(&listing_id).await?.unwrap_or(0);
+        if let Some(listing) = self.market_listings.get(&listing_id).await? {
+            if in_flight >= listing.capacity {
+                return Err(AgentChainError::CapacityExceeded {
+                    agent_id: provider_agent.to_string(),
+                    service_type: service_type.to_string(),
+                    capacity: listing.capacity,
+                });
+            }
+        }
+        self.in_flight.insert(&listing_id, in_flight + 1)?;
+        Ok(())
+    }
+
+    /// Frees the slot taken by a request. Only called by `close_request`, once per
+    /// request, as it leaves its last open status.
+    pub(crate) async fn release_capacity(
+        &mut self,
+        provider_agent: &str,
+        service_type: &str,
+    ) -> Result<(), AgentChainError> {
+        let listing_id = Self::listing_id(provider_agent, service_type);
+        let in_flight = self.in_flight.get(&listing_id).await?.unwrap_or(0);
+        if in_flight <= 1 {
+            self.in_flight.remove(&listing_id)?;
+        } else {
+            self.in_flight.insert(&listing_id, in_flight - 1)?;
+        }
+        Ok(())
+    }
+
+    /// Picks the best-scoring listing for `service_type` priced in `asset` at or below
+    /// `max_price`, skipping paused or expired listings, inactive providers and
+    /// providers without free capacity.
+    pub async fn select_best_provider(
+        &self,
+        requester_agent: &str,
+        service_type: &str,
+        asset: &str,
+        max_price: u128,
+        weights: &ScoringWeights,
+        now: u64,
+    ) -> Result<MarketListing, AgentChainError> {
+        self.rank_providers(requester_agent, service_type, asset, max_price, weights, now)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| AgentChainError::NoProviderAvailable(service_type.to_string()))
+    }
+
+    /// Every listing `select_best_provider` could pick, best score first. Listings with
+    /// equal scores keep the order in which they are stored.
+    pub async fn rank_providers(
+        &self,
+        requester_agent: &str,
+        service_type: &str,
+        asset: &str,
+        max_price: u128,
+        weights: &ScoringWeights,
+        now: u64,
+    ) -> Result<Vec<MarketListing>, AgentChainError> {
+        let mut candidates = Vec::new();
+        self.market_listings
+            .for_each_index_value(|_key, listing| {
+                if listing.is_available(now)
+                    && listing.service_type == service_type
+                    && listing.asset == asset
+                    && listing.price <= max_price
+                    && listing.agent_id != requester_agent
+                {
+                    candidates.push(listing);
+                }
+                Ok(())
+            })
+            .await?;
+
+        let mut ranked = Vec::new();
+        for listing in candidates {
+            let provider = self.get_agent(&listing.agent_id).await?;
+            if !provider.is_active {
+                continue;
+            }
+            if self.in_flight_requests(&listing.agent_id, service_type).await? >= listing.capacity {
+                continue;
+            }
+            let score = weights.score(&listing, &provider, max_price);
+            ranked.push((score, listing));
+        }
+
+        ranked.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        Ok(ranked.into_iter().map(|(_, listing)| listing).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ListingStatus;
+
+    fn listing(price: u128, success_rate: f64) -> MarketListing {
+        MarketListing {
+            agent_id: "provider".to_string(),
+            service_type: "audit".to_string(),
+            asset: "native".to_string(),
+            price,
+            capacity: 1,
+            average_completion_time: 0,
+            success_rate,
+            status: ListingStatus::Active,
+            expires_at: None,
+        }
+    }
+
+    fn provider(reputation: u64) -> Agent {
+        Agent {
+            reputation,
+            ..crate::state::tests::agent("provider")
+        }
+    }
+
+    fn weights(price: u16, reputation: u16, success_rate: u16) -> ScoringWeights {
+        ScoringWeights {
+            price,
+            reputation,
+            success_rate,
+        }
+    }
+
+    #[test]
+    fn scores_average_the_weighted_criteria() {
+        let listing = listing(250, 50.0);
+        let provider = provider(1000);
+        assert_eq!(weights(1, 0, 0).score(&listing, &provider, 1000), 0.75);
+        assert_eq!(weights(0, 1, 0).score(&listing, &provider, 1000), 1.0);
+        assert_eq!(weights(0, 0, 1).score(&listing, &provider, 1000), 0.5);
+        assert_eq!(weights(2, 1, 1).score(&listing, &provider, 1000), 0.75);
+    }
+
+    #[test]
+    fn scores_are_normalized_to_the_unit_range() {
+        let weights = ScoringWeights::default();
+        assert_eq!(weights.score(&listing(0, 250.0), &provider(5000), 1000), 1.0);
+        assert_eq!(weights.score(&listing(0, 100.0), &provider(5000), 0), 1.0);
+        assert_eq!(weights.score(&listing(2000, -5.0), &provider(0), 1000), 0.0);
+    }
+
+    #[test]
+    fn zero_weights_score_nothing() {
+        assert_eq!(weights(0, 0, 0).score(&listing(0, 100.0), &provider(1000), 1000), 0.0);
+    }
+}
@@ -1,4 +1,4 @@
-use async_graphql::{Context, EmptySubscription, Object, Schema, SimpleObject};
+use async_graphql::{Context, EmptySubscription, Object, Schema, SimpleObject, Union};
 use linera_sdk::{
     base::ChainId,
     Service, ServiceRuntime,
@@ -6,7 +6,20 @@ use linera_sdk::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::state::{Agent, AgentChainState, AgentStrategy, MarketListing, ServiceRequest, Transaction};
+use crate::amm::{AmmPool, PRICE_SCALE};
+use crate::credit::{CreditLine, Invoice};
+use crate::governance::{Proposal, ProposalAction};
+use crate::insurance::{InsuranceCover, InsurancePool};
+use crate::jobs::{JobStatus, OpenJob};
+use crate::lending::{LendingConfig, LendingPool};
+use crate::oracle::{DataFeed, FeedValue};
+use crate::orderbook::{Order, OrderStatus};
+use crate::rfq::{Rfq, RfqStatus};
+use crate::state::{
+    Agent, AgentChainState, AgentStrategy, Allowance, MarketListing, Milestone, RevenueSplit,
+    ServiceRequest, SpendingPolicy, StrategyChange, StrategySlot, Transaction, TransactionType,
+};
+use crate::validation::StrategyBounds;
 
 #[derive(SimpleObject)]
 struct AgentInfo {
@@ -14,8 +27,9 @@ struct AgentInfo {
     owner: String,
     name: String,
     description: String,
-    strategy_type: String,
-    balance: String,
+    strategies: Vec<StrategySlotInfo>,
+    strategy_version: u64,
+    balances: Vec<AssetAmountInfo>,
     reputation: u64,
     services_completed: u64,
     services_failed: u64,
@@ -23,6 +37,155 @@ struct AgentInfo {
     is_active: bool,
     created_at: u64,
     last_active: u64,
+    last_withdrawal: Option<u64>,
+    revenue_split: Option<RevenueSplitInfo>,
+}
+
+#[derive(SimpleObject)]
+struct StrategySlotInfo {
+    strategy_type: String,
+    strategy: StrategyInfo,
+    allocation_bps: u16,
+    enabled: bool,
+}
+
+impl From<StrategySlot> for StrategySlotInfo {
+    fn from(slot: StrategySlot) -> Self {
+        StrategySlotInfo {
+            strategy_type: slot.strategy.kind().to_string(),
+            strategy: slot.strategy.into(),
+            allocation_bps: slot.allocation_bps,
+            enabled: slot.enabled,
+        }
+    }
+}
+
+/// An agent strategy with all of its parameters.
+#[derive(Union)]
+enum StrategyInfo {
+    Trading(TradingStrategy),
+    Oracle(OracleStrategy),
+    Governance(GovernanceStrategy),
+    MarketMaker(MarketMakerStrategy),
+    Custom(CustomStrategy),
+}
+
+#[derive(SimpleObject)]
+struct TradingStrategy {
+    risk_level: u8,
+    min_profit: String,
+}
+
+#[derive(SimpleObject)]
+struct OracleStrategy {
+    data_sources: Vec<String>,
+    update_frequency: u64,
+}
+
+#[derive(SimpleObject)]
+struct GovernanceStrategy {
+    voting_power: String,
+    delegation_enabled: bool,
+}
+
+#[derive(SimpleObject)]
+struct MarketMakerStrategy {
+    spread_bps: u16,
+    liquidity_depth: String,
+}
+
+/// Metadata of a `Custom` strategy's external application.
+#[derive(SimpleObject)]
+struct CustomStrategy {
+    application_id: String,
+    schema_id: String,
+    config_size: u32,
+}
+
+impl From<AgentStrategy> for StrategyInfo {
+    fn from(strategy: AgentStrategy) -> Self {
+        match strategy {
+            AgentStrategy::Trading {
+                risk_level,
+                min_profit,
+            } => StrategyInfo::Trading(TradingStrategy {
+                risk_level,
+                min_profit: min_profit.to_string(),
+            }),
+            AgentStrategy::Oracle {
+                data_sources,
+                update_frequency,
+            } => StrategyInfo::Oracle(OracleStrategy {
+                data_sources,
+                update_frequency,
+            }),
+            AgentStrategy::Governance {
+                voting_power,
+                delegation_enabled,
+            } => StrategyInfo::Governance(GovernanceStrategy {
+                voting_power: voting_power.to_string(),
+                delegation_enabled,
+            }),
+            AgentStrategy::MarketMaker {
+                spread_bps,
+                liquidity_depth,
+            } => StrategyInfo::MarketMaker(MarketMakerStrategy {
+                spread_bps,
+                liquidity_depth: liquidity_depth.to_string(),
+            }),
+            AgentStrategy::Custom {
+                application_id,
+                config_blob,
+                schema_id,
+            } => StrategyInfo::Custom(CustomStrategy {
+                application_id: application_id.to_string(),
+                schema_id,
+                config_size: config_blob.len() as u32,
+            }),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct StrategyChangeInfo {
+    version: u64,
+    changed_at: u64,
+    signer: String,
+    strategies: Vec<StrategySlotInfo>,
+    rolled_back_to: Option<u64>,
+}
+
+impl From<StrategyChange> for StrategyChangeInfo {
+    fn from(change: StrategyChange) -> Self {
+        StrategyChangeInfo {
+            version: change.version,
+            changed_at: change.changed_at,
+            signer: change.signer,
+            strategies: change.strategies.into_iter().map(Into::into).collect(),
+            rolled_back_to: change.rolled_back_to,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct RevenueSplitInfo {
+    owner_bps: u16,
+    operator_agent: Option<String>,
+    operator_bps: u16,
+    referrer_agent: Option<String>,
+    referrer_bps: u16,
+}
+
+impl From<RevenueSplit> for RevenueSplitInfo {
+    fn from(split: RevenueSplit) -> Self {
+        RevenueSplitInfo {
+            owner_bps: split.owner_bps,
+            operator_bps: split.operator.as_ref().map_or(0, |operator| operator.bps),
+            operator_agent: split.operator.map(|operator| operator.agent_id),
+            referrer_bps: split.referrer.as_ref().map_or(0, |referrer| referrer.bps),
+            referrer_agent: split.referrer.map(|referrer| referrer.agent_id),
+        }
+    }
 }
 
 impl From<Agent> for AgentInfo {
@@ -34,20 +197,18 @@ impl From<Agent> for AgentInfo {
             0.0
         };
 
-        let strategy_type = match agent.strategy {
-            AgentStrategy::Trading { .. } => "Trading".to_string(),
-            AgentStrategy::Oracle { .. } => "Oracle".to_string(),
-            AgentStrategy::Governance { .. } => "Governance".to_string(),
-            AgentStrategy::MarketMaker { .. } => "MarketMaker".to_string(),
-        };
-
         AgentInfo {
             id: agent.id,
             owner: agent.owner,
             name: agent.name,
             description: agent.description,
-            strategy_type,
-            balance: agent.balance.to_string(),
+            strategies: agent.strategies.into_iter().map(Into::into).collect(),
+            strategy_version: agent.strategy_version,
+            balances: agent
+                .balances
+                .into_iter()
+                .map(|(asset, amount)| AssetAmountInfo::new(asset, amount))
+                .collect(),
             reputation: agent.reputation,
             services_completed: agent.services_completed,
             services_failed: agent.services_failed,
@@ -55,6 +216,23 @@ impl From<Agent> for AgentInfo {
             is_active: agent.is_active,
             created_at: agent.created_at,
             last_active: agent.last_active,
+            last_withdrawal: agent.last_withdrawal,
+            revenue_split: agent.revenue_split.map(RevenueSplitInfo::from),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct AssetAmountInfo {
+    asset: String,
+    amount: String,
+}
+
+impl AssetAmountInfo {
+    fn new(asset: String, amount: u128) -> Self {
+        AssetAmountInfo {
+            asset,
+            amount: amount.to_string(),
         }
     }
 }
@@ -66,10 +244,352 @@ struct ServiceRequestInfo {
     provider_agent: String,
     service_type: String,
     parameters: String,
+    asset: String,
     payment: String,
     status: String,
     created_at: u64,
     completed_at: Option<u64>,
+    milestones: Vec<MilestoneInfo>,
+    milestones_accepted: u32,
+    current_milestone: Option<u32>,
+    escrow: String,
+    released: String,
+    refunded: String,
+    pending_refund: Option<RefundRequestInfo>,
+    insurance: Option<InsuranceCoverInfo>,
+    provider_stake: String,
+    on_credit: bool,
+}
+
+#[derive(SimpleObject)]
+struct InsuranceCoverInfo {
+    premium: String,
+    coverage: String,
+    settled: bool,
+    claim_reason: Option<String>,
+    payout: Option<String>,
+}
+
+impl From<InsuranceCover> for InsuranceCoverInfo {
+    fn from(cover: InsuranceCover) -> Self {
+        InsuranceCoverInfo {
+            premium: cover.premium.to_string(),
+            coverage: cover.coverage.to_string(),
+            settled: cover.settled,
+            claim_reason: cover.claim.as_ref().map(|claim| format!("{:?}", claim.reason)),
+            payout: cover.claim.map(|claim| claim.payout.to_string()),
+        }
+    }
+}
+
+/// Solvency of the insurance pool for one asset.
+#[derive(SimpleObject)]
+struct InsurancePoolInfo {
+    asset: String,
+    balance: String,
+    active_coverage: String,
+    /// Pool balance over open coverage, absent when nothing is covered.
+    coverage_ratio_bps: Option<String>,
+    solvent: bool,
+    premiums_collected: String,
+    claims_paid: String,
+    policies_written: u64,
+    claims_count: u64,
+}
+
+impl From<InsurancePool> for InsurancePoolInfo {
+    fn from(pool: InsurancePool) -> Self {
+        InsurancePoolInfo {
+            coverage_ratio_bps: pool.coverage_ratio_bps().map(|ratio| ratio.to_string()),
+            solvent: pool.balance >= pool.active_coverage,
+            balance: pool.balance.to_string(),
+            active_coverage: pool.active_coverage.to_string(),
+            premiums_collected: pool.premiums_collected.to_string(),
+            claims_paid: pool.claims_paid.to_string(),
+            policies_written: pool.policies_written,
+            claims_count: pool.claims_count,
+            asset: pool.asset,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct InsuranceQuote {
+    provider_agent: String,
+    premium_bps: u32,
+    premium: String,
+    coverage: String,
+}
+
+#[derive(SimpleObject)]
+struct ProposalInfo {
+    id: String,
+    proposer_agent: String,
+    description: String,
+    action_type: String,
+    /// The proposal's action and its parameters.
+    action: String,
+    status: String,
+    votes_for: String,
+    votes_against: String,
+    voter_count: u32,
+    total_voting_power: String,
+    created_at: u64,
+    voting_ends_at: u64,
+    executable_at: u64,
+}
+
+impl From<Proposal> for ProposalInfo {
+    fn from(proposal: Proposal) -> Self {
+        let action_type = match &proposal.action {
+            ProposalAction::UpdateConfig(_) => "UpdateConfig",
+            ProposalAction::TreasurySpend { .. } => "TreasurySpend",
+            ProposalAction::BanAgent { .. } => "BanAgent",
+        };
+        ProposalInfo {
+            id: proposal.id,
+            proposer_agent: proposal.proposer_agent,
+            description: proposal.description,
+            action_type: action_type.to_string(),
+            action: format!("{:?}", proposal.action),
+            status: format!("{:?}", proposal.status),
+            votes_for: proposal.votes_for.to_string(),
+            votes_against: proposal.votes_against.to_string(),
+            voter_count: proposal.voters.len() as u32,
+            total_voting_power: proposal.total_voting_power.to_string(),
+            created_at: proposal.created_at,
+            voting_ends_at: proposal.voting_ends_at,
+            executable_at: proposal.executable_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct FeedValueInfo {
+    value: String,
+    sources: u32,
+    aggregated_at: u64,
+}
+
+impl From<FeedValue> for FeedValueInfo {
+    fn from(feed_value: FeedValue) -> Self {
+        FeedValueInfo {
+            value: feed_value.value.to_string(),
+            sources: feed_value.sources,
+            aggregated_at: feed_value.aggregated_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct FeedSubmissionInfo {
+    agent_id: String,
+    value: String,
+    submitted_at: u64,
+    fresh: bool,
+}
+
+#[derive(SimpleObject)]
+struct DataFeedInfo {
+    id: String,
+    latest: Option<FeedValueInfo>,
+    /// Earlier aggregated values, oldest first.
+    history: Vec<FeedValueInfo>,
+    submissions: Vec<FeedSubmissionInfo>,
+}
+
+impl DataFeedInfo {
+    fn new(feed: DataFeed, now: u64) -> Self {
+        DataFeedInfo {
+            id: feed.id,
+            latest: feed.latest.map(Into::into),
+            history: feed.history.into_iter().map(Into::into).collect(),
+            submissions: feed
+                .submissions
+                .into_iter()
+                .map(|(agent_id, submission)| FeedSubmissionInfo {
+                    agent_id,
+                    value: submission.value.to_string(),
+                    submitted_at: submission.submitted_at,
+                    fresh: submission.is_fresh(now),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct AmmPoolInfo {
+    id: String,
+    asset_a: String,
+    asset_b: String,
+    reserve_a: String,
+    reserve_b: String,
+    total_shares: String,
+    fee_bps: u16,
+    /// Units of `asset_b` per unit of `asset_a`, scaled by `price_scale`.
+    price_of_a: String,
+    price_scale: String,
+    creator_agent: String,
+    swap_count: u64,
+}
+
+impl From<AmmPool> for AmmPoolInfo {
+    fn from(pool: AmmPool) -> Self {
+        AmmPoolInfo {
+            price_of_a: pool.price_of_a().to_string(),
+            price_scale: PRICE_SCALE.to_string(),
+            id: pool.id,
+            asset_a: pool.asset_a,
+            asset_b: pool.asset_b,
+            reserve_a: pool.reserve_a.to_string(),
+            reserve_b: pool.reserve_b.to_string(),
+            total_shares: pool.total_shares.to_string(),
+            fee_bps: pool.fee_bps,
+            creator_agent: pool.creator_agent,
+            swap_count: pool.swap_count,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct OrderInfo {
+    id: String,
+    agent_id: String,
+    base_asset: String,
+    quote_asset: String,
+    side: String,
+    price: String,
+    amount: String,
+    filled: String,
+    status: String,
+    created_at: u64,
+}
+
+impl From<Order> for OrderInfo {
+    fn from(order: Order) -> Self {
+        OrderInfo {
+            id: order.id,
+            agent_id: order.agent_id,
+            base_asset: order.base_asset,
+            quote_asset: order.quote_asset,
+            side: format!("{:?}", order.side),
+            price: order.price.to_string(),
+            amount: order.amount.to_string(),
+            filled: order.filled.to_string(),
+            status: format!("{:?}", order.status),
+            created_at: order.created_at,
+        }
+    }
+}
+
+/// Resting orders of one asset pair, best price first.
+#[derive(SimpleObject)]
+struct OrderBookInfo {
+    base_asset: String,
+    quote_asset: String,
+    bids: Vec<OrderInfo>,
+    asks: Vec<OrderInfo>,
+}
+
+/// Allowed strategy parameter ranges, inclusive, for agent creation forms.
+#[derive(SimpleObject)]
+struct StrategyBoundsInfo {
+    min_risk_level: u8,
+    max_risk_level: u8,
+    max_data_sources: u32,
+    min_update_frequency: u64,
+    max_update_frequency: u64,
+    min_voting_power: String,
+    max_voting_power: String,
+    min_spread_bps: u16,
+    max_spread_bps: u16,
+    min_liquidity_depth: String,
+    max_config_blob_bytes: u32,
+}
+
+impl From<StrategyBounds> for StrategyBoundsInfo {
+    fn from(bounds: StrategyBounds) -> Self {
+        StrategyBoundsInfo {
+            min_risk_level: bounds.min_risk_level,
+            max_risk_level: bounds.max_risk_level,
+            max_data_sources: bounds.max_data_sources as u32,
+            min_update_frequency: bounds.min_update_frequency,
+            max_update_frequency: bounds.max_update_frequency,
+            min_voting_power: bounds.min_voting_power.to_string(),
+            max_voting_power: bounds.max_voting_power.to_string(),
+            min_spread_bps: bounds.min_spread_bps,
+            max_spread_bps: bounds.max_spread_bps,
+            min_liquidity_depth: bounds.min_liquidity_depth.to_string(),
+            max_config_blob_bytes: bounds.max_config_blob_bytes as u32,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct RefundRequestInfo {
+    amount: String,
+    reason: String,
+    requested_at: u64,
+}
+
+impl From<ServiceRequest> for ServiceRequestInfo {
+    fn from(request: ServiceRequest) -> Self {
+        let milestones_accepted = request.milestones_accepted() as u32;
+        let current_milestone = if request.milestones.is_empty() {
+            None
+        } else {
+            request.current_milestone().map(|index| index as u32)
+        };
+
+        ServiceRequestInfo {
+            id: request.id,
+            requester_agent: request.requester_agent,
+            provider_agent: request.provider_agent,
+            service_type: request.service_type,
+            parameters: request.parameters,
+            asset: request.asset,
+            payment: request.payment.to_string(),
+            status: format!("{:?}", request.status),
+            created_at: request.created_at,
+            completed_at: request.completed_at,
+            milestones: request.milestones.into_iter().map(MilestoneInfo::from).collect(),
+            milestones_accepted,
+            current_milestone,
+            escrow: request.escrow.to_string(),
+            released: request.released.to_string(),
+            refunded: request.refunded.to_string(),
+            pending_refund: request.pending_refund.map(|pending| RefundRequestInfo {
+                amount: pending.amount.to_string(),
+                reason: pending.reason,
+                requested_at: pending.requested_at,
+            }),
+            insurance: request.insurance.map(InsuranceCoverInfo::from),
+            provider_stake: request.provider_stake.to_string(),
+            on_credit: request.on_credit,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct MilestoneInfo {
+    description: String,
+    payment: String,
+    status: String,
+    submitted_at: Option<u64>,
+    accepted_at: Option<u64>,
+}
+
+impl From<Milestone> for MilestoneInfo {
+    fn from(milestone: Milestone) -> Self {
+        MilestoneInfo {
+            description: milestone.description,
+            payment: milestone.payment.to_string(),
+            status: format!("{:?}", milestone.status),
+            submitted_at: milestone.submitted_at,
+            accepted_at: milestone.accepted_at,
+        }
+    }
 }
 
 #[derive(SimpleObject)]
@@ -77,9 +597,40 @@ struct TransactionInfo {
     id: String,
     from_agent: String,
     to_agent: String,
+    asset: String,
     amount: String,
     transaction_type: String,
     timestamp: u64,
+    request_id: Option<String>,
+    related_transaction: Option<String>,
+}
+
+impl From<Transaction> for TransactionInfo {
+    fn from(tx: Transaction) -> Self {
+        TransactionInfo {
+            id: tx.id,
+            from_agent: tx.from_agent,
+            to_agent: tx.to_agent,
+            asset: tx.asset,
+            amount: tx.amount.to_string(),
+            transaction_type: format!("{:?}", tx.transaction_type),
+            timestamp: tx.timestamp,
+            request_id: tx.request_id,
+            related_transaction: tx.related_transaction,
+        }
+    }
+}
+
+/// Payments and refunds of a single service request, netted out.
+#[derive(SimpleObject)]
+struct RequestLedger {
+    request_id: String,
+    asset: String,
+    escrow: String,
+    paid: String,
+    refunded: String,
+    net_paid: String,
+    transactions: Vec<TransactionInfo>,
 }
 
 #[derive(SimpleObject)]
@@ -87,18 +638,279 @@ struct MarketplaceStats {
     total_agents: u64,
     active_agents: u64,
     total_transactions: u64,
-    total_volume: String,
+    total_volume: Vec<AssetAmountInfo>,
     average_reputation: f64,
+    lending_pools: Vec<LendingPoolInfo>,
+}
+
+#[derive(SimpleObject)]
+struct LendingPoolInfo {
+    asset: String,
+    total_deposits: String,
+    total_borrowed: String,
+    available_liquidity: String,
+    total_collateral: String,
+    utilization_bps: u32,
+    borrow_rate_bps: u32,
+    last_accrued_at: u64,
+}
+
+impl LendingPoolInfo {
+    fn new(pool: LendingPool, config: &LendingConfig) -> Self {
+        LendingPoolInfo {
+            total_deposits: pool.total_deposits.to_string(),
+            total_borrowed: pool.total_borrowed().to_string(),
+            available_liquidity: pool.available_liquidity().to_string(),
+            total_collateral: pool.total_collateral.to_string(),
+            utilization_bps: pool.utilization_bps() as u32,
+            borrow_rate_bps: pool.borrow_rate_bps(config) as u32,
+            last_accrued_at: pool.last_accrued_at,
+            asset: pool.asset,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct LendingPositionInfo {
+    agent_id: String,
+    asset: String,
+    deposit_shares: String,
+    deposit_value: String,
+    collateral_asset: String,
+    collateral: String,
+    /// Collateral valued in the borrowed asset; missing when its price is unavailable.
+    collateral_value: Option<String>,
+    debt: String,
+    liquidatable: bool,
 }
 
 #[derive(SimpleObject)]
 struct MarketListingInfo {
     agent_id: String,
     service_type: String,
+    asset: String,
     price: String,
     capacity: u32,
+    in_flight: u32,
+    remaining_capacity: u32,
     average_completion_time: u64,
     success_rate: f64,
+    status: String,
+    expires_at: Option<u64>,
+}
+
+impl MarketListingInfo {
+    fn new(listing: MarketListing, in_flight: u32) -> Self {
+        MarketListingInfo {
+            agent_id: listing.agent_id,
+            service_type: listing.service_type,
+            asset: listing.asset,
+            price: listing.price.to_string(),
+            capacity: listing.capacity,
+            in_flight,
+            remaining_capacity: listing.capacity.saturating_sub(in_flight),
+            average_completion_time: listing.average_completion_time,
+            success_rate: listing.success_rate,
+            status: format!("{:?}", listing.status),
+            expires_at: listing.expires_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct AllowanceInfo {
+    owner_agent: String,
+    spender_agent: String,
+    asset: String,
+    amount: String,
+    expires_at: Option<u64>,
+    updated_at: u64,
+}
+
+impl From<Allowance> for AllowanceInfo {
+    fn from(allowance: Allowance) -> Self {
+        AllowanceInfo {
+            owner_agent: allowance.owner_agent,
+            spender_agent: allowance.spender_agent,
+            asset: allowance.asset,
+            amount: allowance.amount.to_string(),
+            expires_at: allowance.expires_at,
+            updated_at: allowance.updated_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct SpendingPolicyInfo {
+    agent_id: String,
+    limits: Vec<SpendingLimitsInfo>,
+    allowed_service_types: Vec<String>,
+    period_seconds: u64,
+}
+
+#[derive(SimpleObject)]
+struct SpendingLimitsInfo {
+    asset: String,
+    max_per_transaction: Option<String>,
+    max_per_period: Option<String>,
+    max_per_counterparty: Option<String>,
+    period_start: Option<u64>,
+    spent_in_period: String,
+}
+
+#[derive(SimpleObject)]
+struct CreditLineInfo {
+    creditor_agent: String,
+    debtor_agent: String,
+    asset: String,
+    limit: String,
+    proposed_limit: Option<String>,
+    outstanding: String,
+    available: String,
+    updated_at: u64,
+}
+
+impl From<CreditLine> for CreditLineInfo {
+    fn from(line: CreditLine) -> Self {
+        CreditLineInfo {
+            available: line.available().to_string(),
+            proposed_limit: line.proposed_limit.map(|limit| limit.to_string()),
+            creditor_agent: line.creditor_agent,
+            debtor_agent: line.debtor_agent,
+            asset: line.asset,
+            limit: line.limit.to_string(),
+            outstanding: line.outstanding.to_string(),
+            updated_at: line.updated_at,
+        }
+    }
+}
+
+/// An agent's credit lines, split by which side of them it is on.
+#[derive(SimpleObject)]
+struct CreditPositions {
+    agent_id: String,
+    as_creditor: Vec<CreditLineInfo>,
+    as_debtor: Vec<CreditLineInfo>,
+}
+
+#[derive(SimpleObject)]
+struct InvoiceInfo {
+    id: String,
+    request_id: String,
+    creditor_agent: String,
+    debtor_agent: String,
+    asset: String,
+    amount: String,
+    description: String,
+    issued_at: u64,
+    due_at: u64,
+    status: String,
+    settled_at: Option<u64>,
+}
+
+impl From<Invoice> for InvoiceInfo {
+    fn from(invoice: Invoice) -> Self {
+        InvoiceInfo {
+            id: invoice.id,
+            request_id: invoice.request_id,
+            creditor_agent: invoice.creditor_agent,
+            debtor_agent: invoice.debtor_agent,
+            asset: invoice.asset,
+            amount: invoice.amount.to_string(),
+            description: invoice.description,
+            issued_at: invoice.issued_at,
+            due_at: invoice.due_at,
+            status: format!("{:?}", invoice.status),
+            settled_at: invoice.settled_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct RfqInfo {
+    id: String,
+    requester_agent: String,
+    service_type: String,
+    parameters: String,
+    asset: String,
+    max_budget: String,
+    bidding_deadline: u64,
+    insured: bool,
+    status: String,
+    bids: Vec<BidInfo>,
+    awarded_provider: Option<String>,
+    request_id: Option<String>,
+    created_at: u64,
+}
+
+#[derive(SimpleObject)]
+struct BidInfo {
+    provider_agent: String,
+    price: String,
+    submitted_at: u64,
+}
+
+impl From<Rfq> for RfqInfo {
+    fn from(rfq: Rfq) -> Self {
+        RfqInfo {
+            id: rfq.id,
+            requester_agent: rfq.requester_agent,
+            service_type: rfq.service_type,
+            parameters: rfq.parameters,
+            asset: rfq.asset,
+            max_budget: rfq.max_budget.to_string(),
+            bidding_deadline: rfq.bidding_deadline,
+            insured: rfq.insured,
+            status: format!("{:?}", rfq.status),
+            bids: rfq
+                .bids
+                .into_iter()
+                .map(|bid| BidInfo {
+                    provider_agent: bid.provider_agent,
+                    price: bid.price.to_string(),
+                    submitted_at: bid.submitted_at,
+                })
+                .collect(),
+            awarded_provider: rfq.awarded_provider,
+            request_id: rfq.request_id,
+            created_at: rfq.created_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct OpenJobInfo {
+    id: String,
+    requester_agent: String,
+    service_type: String,
+    parameters: String,
+    asset: String,
+    payment: String,
+    min_reputation: u64,
+    required_stake: String,
+    status: String,
+    claimed_by: Option<String>,
+    request_id: Option<String>,
+    created_at: u64,
+}
+
+impl From<OpenJob> for OpenJobInfo {
+    fn from(job: OpenJob) -> Self {
+        OpenJobInfo {
+            id: job.id,
+            requester_agent: job.requester_agent,
+            service_type: job.service_type,
+            parameters: job.parameters,
+            asset: job.asset,
+            payment: job.payment.to_string(),
+            min_reputation: job.min_reputation,
+            required_stake: job.required_stake.to_string(),
+            status: format!("{:?}", job.status),
+            claimed_by: job.claimed_by,
+            request_id: job.request_id,
+            created_at: job.created_at,
+        }
+    }
 }
 
 pub struct QueryRoot;
@@ -137,19 +949,24 @@ impl QueryRoot {
         agents
     }
 
-    async fn agents_by_strategy(&self, ctx: &Context<'_>, strategy_type: String) -> Vec<AgentInfo> {
+    /// Agents running `strategy_type`, among their other strategies. Disabled
+    /// strategies only count when `include_disabled` is set.
+    async fn agents_by_strategy(
+        &self,
+        ctx: &Context<'_>,
+        strategy_type: String,
+        include_disabled: Option<bool>,
+    ) -> Vec<AgentInfo> {
         let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let include_disabled = include_disabled.unwrap_or(false);
         let mut agents = Vec::new();
         
         state.agents.for_each_index_value(|_key, agent| {
-            let agent_strategy_type = match &agent.strategy {
-                AgentStrategy::Trading { .. } => "Trading",
-                AgentStrategy::Oracle { .. } => "Oracle",
-                AgentStrategy::Governance { .. } => "Governance",
-                AgentStrategy::MarketMaker { .. } => "MarketMaker",
-            };
-
-            if agent_strategy_type == strategy_type {
+            let runs_strategy = agent
+                .strategy_slot(&strategy_type)
+                .is_some_and(|slot| include_disabled || slot.enabled);
+
+            if runs_strategy {
                 agents.push(agent.into());
             }
             Ok(())
@@ -158,21 +975,37 @@ impl QueryRoot {
         agents
     }
 
+    /// Every version of the agent's strategies, oldest first.
+    async fn strategy_history(&self, ctx: &Context<'_>, agent_id: String) -> Vec<StrategyChangeInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        state
+            .get_strategy_history(&agent_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
+
     async fn service_request(&self, ctx: &Context<'_>, request_id: String) -> Option<ServiceRequestInfo> {
         let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
         let request = state.service_requests.get(&request_id).await.ok()??;
-        
-        Some(ServiceRequestInfo {
-            id: request.id,
-            requester_agent: request.requester_agent,
-            provider_agent: request.provider_agent,
-            service_type: request.service_type,
-            parameters: request.parameters,
-            payment: request.payment.to_string(),
-            status: format!("{:?}", request.status),
-            created_at: request.created_at,
-            completed_at: request.completed_at,
-        })
+        Some(request.into())
+    }
+
+    /// Requests in which the agent is either the requester or the provider.
+    async fn agent_requests(&self, ctx: &Context<'_>, agent_id: String) -> Vec<ServiceRequestInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut requests = Vec::new();
+
+        state.service_requests.for_each_index_value(|_key, request| {
+            if request.requester_agent == agent_id || request.provider_agent == agent_id {
+                requests.push(request.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        requests
     }
 
     async fn pending_requests(&self, ctx: &Context<'_>) -> Vec<ServiceRequestInfo> {
@@ -181,17 +1014,7 @@ impl QueryRoot {
         
         state.service_requests.for_each_index_value(|_key, request| {
             if matches!(request.status, crate::state::ServiceStatus::Pending) {
-                requests.push(ServiceRequestInfo {
-                    id: request.id,
-                    requester_agent: request.requester_agent,
-                    provider_agent: request.provider_agent,
-                    service_type: request.service_type,
-                    parameters: request.parameters,
-                    payment: request.payment.to_string(),
-                    status: format!("{:?}", request.status),
-                    created_at: request.created_at,
-                    completed_at: request.completed_at,
-                });
+                requests.push(request.into());
             }
             Ok(())
         }).await.ok();
@@ -206,14 +1029,7 @@ impl QueryRoot {
         
         state.transactions.for_each_index_value(|_key, tx| {
             if transactions.len() < limit {
-                transactions.push(TransactionInfo {
-                    id: tx.id,
-                    from_agent: tx.from_agent,
-                    to_agent: tx.to_agent,
-                    amount: tx.amount.to_string(),
-                    transaction_type: format!("{:?}", tx.transaction_type),
-                    timestamp: tx.timestamp,
-                });
+                transactions.push(tx.into());
             }
             Ok(())
         }).await.ok();
@@ -221,12 +1037,46 @@ impl QueryRoot {
         transactions
     }
 
+    async fn request_ledger(&self, ctx: &Context<'_>, request_id: String) -> Option<RequestLedger> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let request = state.service_requests.get(&request_id).await.ok()??;
+
+        let mut paid = 0u128;
+        let mut refunded = 0u128;
+        let mut transactions = Vec::new();
+        state.transactions.for_each_index_value(|_key, tx| {
+            if tx.request_id.as_deref() == Some(request_id.as_str()) {
+                match tx.transaction_type {
+                    TransactionType::ServicePayment => paid += tx.amount,
+                    TransactionType::Refund => refunded += tx.amount,
+                    _ => {}
+                }
+                transactions.push(tx.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        Some(RequestLedger {
+            request_id,
+            asset: request.asset,
+            escrow: request.escrow.to_string(),
+            paid: paid.to_string(),
+            refunded: refunded.to_string(),
+            net_paid: paid.saturating_sub(refunded).to_string(),
+            transactions,
+        })
+    }
+
     async fn marketplace_stats(&self, ctx: &Context<'_>) -> MarketplaceStats {
         let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
         
         let total_agents = state.total_agents.get();
         let total_transactions = state.total_transactions.get();
-        let total_volume = state.total_volume.get();
+        let mut total_volume = Vec::new();
+        state.total_volume.for_each_index_value(|asset, volume| {
+            total_volume.push(AssetAmountInfo::new(asset, volume));
+            Ok(())
+        }).await.ok();
 
         let mut active_count = 0u64;
         let mut total_reputation = 0u64;
@@ -239,6 +1089,13 @@ impl QueryRoot {
             Ok(())
         }).await.ok();
 
+        let lending_config = state.config.get().lending.clone();
+        let mut lending_pools = Vec::new();
+        state.lending_pools.for_each_index_value(|_asset, pool| {
+            lending_pools.push(LendingPoolInfo::new(pool, &lending_config));
+            Ok(())
+        }).await.ok();
+
         let average_reputation = if total_agents > 0 {
             total_reputation as f64 / total_agents as f64
         } else {
@@ -249,33 +1106,445 @@ impl QueryRoot {
             total_agents,
             active_agents: active_count,
             total_transactions,
-            total_volume: total_volume.to_string(),
+            total_volume,
             average_reputation,
+            lending_pools,
+        }
+    }
+
+    /// The agent's spending guardrails, with what it has spent in the current period.
+    async fn spending_policy(&self, ctx: &Context<'_>, agent_id: String) -> Option<SpendingPolicyInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let policy: SpendingPolicy = state.spending_policies.get(&agent_id).await.ok()??;
+
+        let mut limits = Vec::new();
+        for (asset, asset_limits) in policy.limits.iter() {
+            let key = AgentChainState::<ServiceRuntime>::spending_window_key(&agent_id, asset);
+            let window = state.spending_windows.get(&key).await.ok().flatten();
+            limits.push(SpendingLimitsInfo {
+                asset: asset.clone(),
+                max_per_transaction: asset_limits.max_per_transaction.map(|max| max.to_string()),
+                max_per_period: asset_limits.max_per_period.map(|max| max.to_string()),
+                max_per_counterparty: asset_limits.max_per_counterparty.map(|max| max.to_string()),
+                period_start: window.as_ref().map(|window| window.period_start),
+                spent_in_period: window.map_or(0, |window| window.spent).to_string(),
+            });
         }
+
+        Some(SpendingPolicyInfo {
+            agent_id,
+            limits,
+            period_seconds: policy.period_seconds(),
+            allowed_service_types: policy.allowed_service_types,
+        })
+    }
+
+    async fn allowance(
+        &self,
+        ctx: &Context<'_>,
+        owner_agent: String,
+        spender_agent: String,
+        asset: String,
+    ) -> Option<AllowanceInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let key = AgentChainState::<ServiceRuntime>::allowance_key(&owner_agent, &spender_agent, &asset);
+        let allowance = state.allowances.get(&key).await.ok()??;
+        Some(allowance.into())
     }
 
-    async fn market_listings(&self, ctx: &Context<'_>) -> Vec<MarketListingInfo> {
+    /// Allowances granted by `owner_agent` and/or held by `spender_agent`.
+    async fn allowances(
+        &self,
+        ctx: &Context<'_>,
+        owner_agent: Option<String>,
+        spender_agent: Option<String>,
+    ) -> Vec<AllowanceInfo> {
         let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut allowances = Vec::new();
+
+        state.allowances.for_each_index_value(|_key, allowance| {
+            let owner_matches = owner_agent.as_ref().map_or(true, |owner| &allowance.owner_agent == owner);
+            let spender_matches = spender_agent.as_ref().map_or(true, |spender| &allowance.spender_agent == spender);
+            if owner_matches && spender_matches {
+                allowances.push(allowance.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        allowances
+    }
+
+    /// Unclaimed jobs, optionally narrowed to a service type, to jobs whose reputation and
+    /// stake requirements stay within the given bounds, or to jobs a given agent can claim.
+    async fn open_jobs(
+        &self,
+        ctx: &Context<'_>,
+        service_type: Option<String>,
+        max_min_reputation: Option<u64>,
+        max_required_stake: Option<String>,
+        eligible_agent: Option<String>,
+    ) -> Vec<OpenJobInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let max_required_stake = max_required_stake.and_then(|stake| stake.parse::<u128>().ok());
+        let eligible_agent = match eligible_agent {
+            Some(agent_id) => match state.agents.get(&agent_id).await.ok().flatten() {
+                Some(agent) => Some(agent),
+                None => return Vec::new(),
+            },
+            None => None,
+        };
+        let mut jobs = Vec::new();
+
+        state.open_jobs.for_each_index_value(|_key, job| {
+            let matches = job.status == JobStatus::Open
+                && service_type.as_ref().map_or(true, |service_type| &job.service_type == service_type)
+                && max_min_reputation.map_or(true, |max| job.min_reputation <= max)
+                && max_required_stake.map_or(true, |max| job.required_stake <= max)
+                && eligible_agent.as_ref().map_or(true, |agent| job.ineligibility(agent).is_none());
+            if matches {
+                jobs.push(job.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        jobs
+    }
+
+    async fn rfq(&self, ctx: &Context<'_>, rfq_id: String) -> Option<RfqInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let rfq = state.rfqs.get(&rfq_id).await.ok()??;
+        Some(rfq.into())
+    }
+
+    async fn open_rfqs(&self, ctx: &Context<'_>, service_type: Option<String>) -> Vec<RfqInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut rfqs = Vec::new();
+
+        state.rfqs.for_each_index_value(|_key, rfq| {
+            let type_matches = service_type.as_ref().map_or(true, |service_type| &rfq.service_type == service_type);
+            if rfq.status == RfqStatus::Open && type_matches {
+                rfqs.push(rfq.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        rfqs
+    }
+
+    async fn insurance_pools(&self, ctx: &Context<'_>) -> Vec<InsurancePoolInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut pools = Vec::new();
+
+        state.insurance_pools.for_each_index_value(|_asset, pool| {
+            pools.push(pool.into());
+            Ok(())
+        }).await.ok();
+
+        pools
+    }
+
+    /// Premium and coverage an insured request to `provider_agent` would get today.
+    async fn insurance_quote(
+        &self,
+        ctx: &Context<'_>,
+        provider_agent: String,
+        payment: String,
+    ) -> Option<InsuranceQuote> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let provider = state.agents.get(&provider_agent).await.ok()??;
+        let payment = payment.parse::<u128>().ok()?;
+        let config = &state.config.get().insurance;
+        let (premium, coverage) = config.quote(&provider, payment);
+
+        Some(InsuranceQuote {
+            provider_agent,
+            premium_bps: config.premium_bps(&provider) as u32,
+            premium: premium.to_string(),
+            coverage: coverage.to_string(),
+        })
+    }
+
+    /// Proposals, optionally filtered by status (`Active`, `Executed` or `Rejected`).
+    async fn proposals(&self, ctx: &Context<'_>, status: Option<String>) -> Vec<ProposalInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut proposals = Vec::new();
+
+        state.proposals.for_each_index_value(|_key, proposal| {
+            let status_matches = status
+                .as_ref()
+                .map_or(true, |status| &format!("{:?}", proposal.status) == status);
+            if status_matches {
+                proposals.push(proposal.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        proposals
+    }
+
+    async fn proposal(&self, ctx: &Context<'_>, proposal_id: String) -> Option<ProposalInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        state.get_proposal(&proposal_id).await.ok().map(Into::into)
+    }
+
+    /// Voting power the agent would cast now, including votes delegated to it.
+    async fn voting_power(&self, ctx: &Context<'_>, agent_id: String) -> String {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        state
+            .effective_voting_power(&agent_id)
+            .await
+            .unwrap_or(0)
+            .to_string()
+    }
+
+    async fn delegate_of(&self, ctx: &Context<'_>, agent_id: String) -> Option<String> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        state.delegations.get(&agent_id).await.ok()?
+    }
+
+    async fn treasury(&self, ctx: &Context<'_>) -> Vec<AssetAmountInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut balances = Vec::new();
+
+        state.treasury.for_each_index_value(|asset, amount| {
+            balances.push(AssetAmountInfo {
+                asset,
+                amount: amount.to_string(),
+            });
+            Ok(())
+        }).await.ok();
+
+        balances
+    }
+
+    async fn data_feed(&self, ctx: &Context<'_>, feed_id: String) -> Option<DataFeedInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let feed = state.get_data_feed(&feed_id).await.ok()?;
+        let now = current_timestamp(ctx);
+        Some(DataFeedInfo::new(feed, now))
+    }
+
+    async fn data_feed_ids(&self, ctx: &Context<'_>) -> Vec<String> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        state.data_feeds.indices().await.unwrap_or_default()
+    }
+
+    async fn amm_pools(&self, ctx: &Context<'_>) -> Vec<AmmPoolInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut pools = Vec::new();
+
+        state.amm_pools.for_each_index_value(|_key, pool| {
+            pools.push(pool.into());
+            Ok(())
+        }).await.ok();
+
+        pools
+    }
+
+    /// The pool between two assets, in either order.
+    async fn amm_pool(
+        &self,
+        ctx: &Context<'_>,
+        asset_x: String,
+        asset_y: String,
+    ) -> Option<AmmPoolInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let pool_id = AgentChainState::<ServiceRuntime>::amm_pool_id(&asset_x, &asset_y);
+        state.get_amm_pool(&pool_id).await.ok().map(Into::into)
+    }
+
+    /// What swapping `amount_in` of `asset_in` would return right now, after the fee.
+    async fn swap_quote(
+        &self,
+        ctx: &Context<'_>,
+        pool_id: String,
+        asset_in: String,
+        amount_in: String,
+    ) -> Option<String> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let pool = state.get_amm_pool(&pool_id).await.ok()?;
+        let amount_in = amount_in.parse::<u128>().ok()?;
+        pool.quote(&asset_in, amount_in).map(|amount_out| amount_out.to_string())
+    }
+
+    async fn lp_shares(&self, ctx: &Context<'_>, pool_id: String, agent_id: String) -> String {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        state
+            .lp_shares_of(&pool_id, &agent_id)
+            .await
+            .unwrap_or(0)
+            .to_string()
+    }
+
+    async fn order_book(
+        &self,
+        ctx: &Context<'_>,
+        base_asset: String,
+        quote_asset: String,
+    ) -> Option<OrderBookInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let book = state.get_order_book(&base_asset, &quote_asset).await.ok()?;
+
+        let mut bids = Vec::new();
+        for order_id in &book.bids {
+            bids.push(state.get_order(order_id).await.ok()?.into());
+        }
+        let mut asks = Vec::new();
+        for order_id in &book.asks {
+            asks.push(state.get_order(order_id).await.ok()?.into());
+        }
+
+        Some(OrderBookInfo {
+            base_asset,
+            quote_asset,
+            bids,
+            asks,
+        })
+    }
+
+    /// The agent's orders, optionally only the open ones.
+    async fn orders(
+        &self,
+        ctx: &Context<'_>,
+        agent_id: String,
+        open_only: Option<bool>,
+    ) -> Vec<OrderInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let open_only = open_only.unwrap_or(false);
+        let mut orders = Vec::new();
+
+        state.orders.for_each_index_value(|_key, order| {
+            if order.agent_id == agent_id && (!open_only || order.status == OrderStatus::Open) {
+                orders.push(order.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        orders
+    }
+
+    /// Ranges that `createAgent` and `updateStrategy` accept for strategy parameters.
+    async fn strategy_bounds(&self, ctx: &Context<'_>) -> StrategyBoundsInfo {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        state.config.get().strategy_bounds.clone().into()
+    }
+
+    async fn lending_position(
+        &self,
+        ctx: &Context<'_>,
+        agent_id: String,
+        asset: String,
+    ) -> Option<LendingPositionInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok()?;
+        let key = AgentChainState::<ServiceRuntime>::lending_position_key(&agent_id, &asset);
+        let position = state.lending_positions.get(&key).await.ok()??;
+        let pool = state.lending_pools.get(&asset).await.ok()??;
+        let collateral_value = state
+            .collateral_value(&position, current_timestamp(ctx))
+            .await
+            .ok();
+
+        Some(LendingPositionInfo {
+            deposit_shares: position.deposit_shares.to_string(),
+            deposit_value: position.deposit_value(&pool).to_string(),
+            collateral: position.collateral.to_string(),
+            collateral_value: collateral_value.map(|value| value.to_string()),
+            debt: position.debt(&pool).to_string(),
+            liquidatable: collateral_value.is_some_and(|value| {
+                position.is_liquidatable(&pool, &state.config.get().lending, value)
+            }),
+            collateral_asset: position.collateral_asset,
+            agent_id,
+            asset,
+        })
+    }
+
+    async fn credit_positions(&self, ctx: &Context<'_>, agent_id: String) -> CreditPositions {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut as_creditor = Vec::new();
+        let mut as_debtor = Vec::new();
+
+        state.credit_lines.for_each_index_value(|_key, line| {
+            if line.creditor_agent == agent_id {
+                as_creditor.push(line.into());
+            } else if line.debtor_agent == agent_id {
+                as_debtor.push(line.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        CreditPositions {
+            agent_id,
+            as_creditor,
+            as_debtor,
+        }
+    }
+
+    /// Invoices the agent has issued or owes, optionally filtered by status.
+    async fn invoices(
+        &self,
+        ctx: &Context<'_>,
+        agent_id: String,
+        status: Option<String>,
+    ) -> Vec<InvoiceInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let mut invoices = Vec::new();
+
+        state.invoices.for_each_index_value(|_key, invoice| {
+            let involved = invoice.creditor_agent == agent_id || invoice.debtor_agent == agent_id;
+            let status_matches = status
+                .as_ref()
+                .map_or(true, |status| &format!("{:?}", invoice.status) == status);
+            if involved && status_matches {
+                invoices.push(invoice.into());
+            }
+            Ok(())
+        }).await.ok();
+
+        invoices
+    }
+
+    /// Listings on offer. Paused and expired listings are only included when
+    /// `include_inactive` is set.
+    async fn market_listings(
+        &self,
+        ctx: &Context<'_>,
+        include_inactive: Option<bool>,
+    ) -> Vec<MarketListingInfo> {
+        let state = ctx.data::<Arc<AgentChainState<ServiceRuntime>>>().ok().unwrap();
+        let include_inactive = include_inactive.unwrap_or(false);
+        let now = current_timestamp(ctx);
         let mut listings = Vec::new();
         
         state.market_listings.for_each_index_value(|_key, listing| {
-            listings.push(MarketListingInfo {
-                agent_id: listing.agent_id,
-                service_type: listing.service_type,
-                price: listing.price.to_string(),
-                capacity: listing.capacity,
-                average_completion_time: listing.average_completion_time,
-                success_rate: listing.success_rate,
-            });
+            if include_inactive || listing.is_available(now) {
+                listings.push(listing);
+            }
             Ok(())
         }).await.ok();
 
-        listings
+        let mut infos = Vec::new();
+        for listing in listings {
+            let in_flight = state
+                .in_flight_requests(&listing.agent_id, &listing.service_type)
+                .await
+                .unwrap_or(0);
+            infos.push(MarketListingInfo::new(listing, in_flight));
+        }
+        infos
     }
 }
 
 pub struct AgentChainService {
     state: Arc<AgentChainState<ServiceRuntime>>,
+    runtime: Arc<ServiceRuntime<AgentChainService>>,
+}
+
+/// Seconds since the epoch according to the chain's clock.
+fn current_timestamp(ctx: &Context<'_>) -> u64 {
+    ctx.data::<Arc<ServiceRuntime<AgentChainService>>>()
+        .map(|runtime| runtime.system_time().micros() / 1_000_000)
+        .unwrap_or_default()
 }
 
 impl Service for AgentChainService {
@@ -287,12 +1556,14 @@ impl Service for AgentChainService {
             .expect("Failed to load state");
         AgentChainService {
             state: Arc::new(state),
+            runtime: Arc::new(runtime),
         }
     }
 
     async fn handle_query(&self, query: &[u8]) -> Vec<u8> {
         let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
             .data(self.state.clone())
+            .data(self.runtime.clone())
             .finish();
 
         let query_str = String::from_utf8_lossy(query);
@@ -305,3 +1576,70 @@ struct EmptyMutation;
 
 #[Object]
 impl EmptyMutation {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_expose_their_kind_and_settings() {
+        let slot = StrategySlot {
+            strategy: AgentStrategy::Trading {
+                risk_level: 7,
+                min_profit: u128::MAX,
+            },
+            allocation_bps: 2_500,
+            enabled: false,
+        };
+        let info = StrategySlotInfo::from(slot);
+        assert_eq!(info.strategy_type, "Trading");
+        assert_eq!(info.allocation_bps, 2_500);
+        assert!(!info.enabled);
+        match info.strategy {
+            StrategyInfo::Trading(trading) => {
+                assert_eq!(trading.risk_level, 7);
+                assert_eq!(trading.min_profit, u128::MAX.to_string());
+            }
+            _ => panic!("expected a Trading strategy"),
+        }
+    }
+
+    #[test]
+    fn strategy_parameters_map_to_their_union_member() {
+        let oracle = AgentStrategy::Oracle {
+            data_sources: vec!["a".to_string(), "b".to_string()],
+            update_frequency: 60,
+        };
+        match StrategyInfo::from(oracle) {
+            StrategyInfo::Oracle(oracle) => {
+                assert_eq!(oracle.data_sources, ["a", "b"]);
+                assert_eq!(oracle.update_frequency, 60);
+            }
+            _ => panic!("expected an Oracle strategy"),
+        }
+
+        let governance = AgentStrategy::Governance {
+            voting_power: 1_000,
+            delegation_enabled: true,
+        };
+        match StrategyInfo::from(governance) {
+            StrategyInfo::Governance(governance) => {
+                assert_eq!(governance.voting_power, "1000");
+                assert!(governance.delegation_enabled);
+            }
+            _ => panic!("expected a Governance strategy"),
+        }
+
+        let market_maker = AgentStrategy::MarketMaker {
+            spread_bps: 30,
+            liquidity_depth: 5_000,
+        };
+        match StrategyInfo::from(market_maker) {
+            StrategyInfo::MarketMaker(market_maker) => {
+                assert_eq!(market_maker.spread_bps, 30);
+                assert_eq!(market_maker.liquidity_depth, "5000");
+            }
+            _ => panic!("expected a MarketMaker strategy"),
+        }
+    }
+}
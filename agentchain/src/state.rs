@@ -1,7 +1,27 @@
-use linera_sdk::views::{MapView, RegisterView, RootView, View, ViewStorageContext};
+use std::collections::BTreeMap;
+
+use linera_sdk::{
+    base::ApplicationId,
+    views::{MapView, RegisterView, RootView, View, ViewStorageContext},
+};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::amm::AmmPool;
+use crate::credit::{CreditLine, Invoice};
+use crate::governance::{GovernanceConfig, Proposal};
+use crate::insurance::{ClaimReason, InsuranceConfig, InsuranceCover, InsurancePool};
+use crate::lending::{LendingConfig, LendingPool, LendingPosition};
+use crate::jobs::OpenJob;
+use crate::math::mul_div;
+use crate::oracle::{DataFeed, OracleConfig};
+use crate::orderbook::{Order, OrderBook};
+use crate::rfq::Rfq;
+use crate::validation::StrategyBounds;
+
+/// Asset id of the chain's native token.
+pub const NATIVE_ASSET: &str = "native";
+
 #[derive(Debug, Error)]
 pub enum AgentChainError {
     #[error("Agent not found: {0}")]
@@ -12,9 +32,74 @@ pub enum AgentChainError {
     
     #[error("Unauthorized operation")]
     Unauthorized,
+
+    #[error("Insufficient allowance: required {required}, available {available}")]
+    InsufficientAllowance { required: u128, available: u128 },
+
+    #[error("Allowance expired at {0}")]
+    AllowanceExpired(u64),
+
+    #[error("Spending limit exceeded ({kind:?}): allowed {allowed}, attempted {attempted}")]
+    SpendingLimitExceeded {
+        kind: SpendingLimitKind,
+        allowed: u128,
+        attempted: u128,
+    },
+
+    #[error("Service type not allowed by spending policy: {0}")]
+    ServiceTypeNotAllowed(String),
+
+    #[error("Withdrawal cooldown active until {available_at}")]
+    WithdrawalCooldown { available_at: u64 },
+
+    #[error("Invalid revenue split: {0}")]
+    InvalidRevenueSplit(String),
+
+    #[error("Credit limit exceeded: limit {limit}, requested {requested}")]
+    CreditLimitExceeded { limit: u128, requested: u128 },
+
+    #[error("Invoice error: {0}")]
+    InvoiceFailed(String),
+
+    #[error("Lending operation failed: {0}")]
+    LendingFailed(String),
+
+    #[error("RFQ failed: {0}")]
+    RfqFailed(String),
+
+    #[error("Job failed: {0}")]
+    JobFailed(String),
+
+    #[error("Governance failed: {0}")]
+    GovernanceFailed(String),
+
+    #[error("Oracle failed: {0}")]
+    OracleFailed(String),
+
+    #[error("AMM failed: {0}")]
+    AmmFailed(String),
+
+    #[error("Order failed: {0}")]
+    OrderFailed(String),
+
+    #[error("Listing failed: {0}")]
+    ListingFailed(String),
+
+    #[error("No provider available for service type: {0}")]
+    NoProviderAvailable(String),
+
+    #[error("Agent {agent_id} is at capacity ({capacity}) for {service_type}")]
+    CapacityExceeded {
+        agent_id: String,
+        service_type: String,
+        capacity: u32,
+    },
+
+    #[error("Agent {agent_id} is not eligible for this job: {reason}")]
+    NotEligible { agent_id: String, reason: String },
     
-    #[error("Invalid strategy type")]
-    InvalidStrategy,
+    #[error("Invalid strategy {field}: {reason}")]
+    InvalidStrategy { field: String, reason: String },
     
     #[error("Service request failed: {0}")]
     ServiceRequestFailed(String),
@@ -26,6 +111,51 @@ pub enum AgentStrategy {
     Oracle { data_sources: Vec<String>, update_frequency: u64 },
     Governance { voting_power: u128, delegation_enabled: bool },
     MarketMaker { spread_bps: u16, liquidity_depth: u128 },
+    /// Behavior provided by a user-deployed application implementing
+    /// `CustomStrategyAbi`, which AgentChain consults before the agent takes a request.
+    Custom {
+        application_id: ApplicationId,
+        /// Strategy settings, opaque to AgentChain and passed to the application.
+        config_blob: Vec<u8>,
+        /// Identifies the format of `config_blob`.
+        schema_id: String,
+    },
+}
+
+impl AgentStrategy {
+    /// Name of the variant, which also identifies the agent's slot for this strategy.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AgentStrategy::Trading { .. } => "Trading",
+            AgentStrategy::Oracle { .. } => "Oracle",
+            AgentStrategy::Governance { .. } => "Governance",
+            AgentStrategy::MarketMaker { .. } => "MarketMaker",
+            AgentStrategy::Custom { .. } => "Custom",
+        }
+    }
+}
+
+/// One of the strategies an agent runs. An agent has at most one slot per strategy kind.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StrategySlot {
+    pub strategy: AgentStrategy,
+    /// Share of the agent's capital the strategy may put to work. Allocations of all
+    /// slots add up to at most 100%.
+    pub allocation_bps: u16,
+    pub enabled: bool,
+}
+
+/// One version of an agent's strategies, recorded whenever they change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrategyChange {
+    /// Starts at 0 when the agent is created.
+    pub version: u64,
+    pub changed_at: u64,
+    /// Owner that signed the change.
+    pub signer: String,
+    pub strategies: Vec<StrategySlot>,
+    /// Version this change restored, for rollbacks.
+    pub rolled_back_to: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,14 +164,83 @@ pub struct Agent {
     pub owner: String,
     pub name: String,
     pub description: String,
-    pub strategy: AgentStrategy,
-    pub balance: u128,
+    pub strategies: Vec<StrategySlot>,
+    /// Version of `strategies` in the agent's strategy history.
+    pub strategy_version: u64,
+    pub balances: BTreeMap<String, u128>,
     pub reputation: u64,
     pub services_completed: u64,
     pub services_failed: u64,
     pub created_at: u64,
     pub last_active: u64,
     pub is_active: bool,
+    pub last_withdrawal: Option<u64>,
+    pub revenue_split: Option<RevenueSplit>,
+}
+
+impl Agent {
+    pub fn strategy_slot(&self, kind: &str) -> Option<&StrategySlot> {
+        self.strategies.iter().find(|slot| slot.strategy.kind() == kind)
+    }
+
+    /// The enabled slot of the given kind, if any.
+    pub fn enabled_strategy(&self, kind: &str) -> Option<&StrategySlot> {
+        self.strategy_slot(kind).filter(|slot| slot.enabled)
+    }
+
+    pub fn balance_of(&self, asset: &str) -> u128 {
+        self.balances.get(asset).copied().unwrap_or(0)
+    }
+
+    pub fn credit(&mut self, asset: &str, amount: u128) {
+        *self.balances.entry(asset.to_string()).or_insert(0) += amount;
+    }
+
+    pub fn debit(&mut self, asset: &str, amount: u128) -> Result<(), AgentChainError> {
+        let available = self.balance_of(asset);
+        if available < amount {
+            return Err(AgentChainError::InsufficientBalance {
+                required: amount,
+                available,
+            });
+        }
+        self.balances.insert(asset.to_string(), available - amount);
+        Ok(())
+    }
+}
+
+/// How an agent's service earnings are divided, in basis points adding up to 10 000.
+///
+/// The owner's share stays with the agent itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevenueSplit {
+    pub owner_bps: u16,
+    pub operator: Option<Beneficiary>,
+    pub referrer: Option<Beneficiary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Beneficiary {
+    pub agent_id: String,
+    pub bps: u16,
+}
+
+impl RevenueSplit {
+    pub const TOTAL_BPS: u32 = 10_000;
+
+    /// Splits `amount` into `(agent_id, share)` legs. Rounding leftovers go to the owner.
+    pub fn legs(&self, owner_agent: &str, amount: u128) -> Vec<(String, u128)> {
+        let mut legs = Vec::new();
+        let mut distributed = 0;
+        for beneficiary in self.operator.iter().chain(self.referrer.iter()) {
+            let share = mul_div(amount, beneficiary.bps as u128, Self::TOTAL_BPS as u128)
+                .unwrap_or(0);
+            distributed += share;
+            legs.push((beneficiary.agent_id.clone(), share));
+        }
+        legs.insert(0, (owner_agent.to_string(), amount - distributed));
+        legs
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,10 +250,155 @@ pub struct ServiceRequest {
     pub provider_agent: String,
     pub service_type: String,
     pub parameters: String,
+    pub asset: String,
     pub payment: u128,
     pub status: ServiceStatus,
     pub created_at: u64,
     pub completed_at: Option<u64>,
+    pub milestones: Vec<Milestone>,
+    pub escrow: u128,
+    pub released: u128,
+    pub refunded: u128,
+    /// Payments released to the provider and its revenue split beneficiaries.
+    pub payouts: Vec<Payout>,
+    pub pending_refund: Option<RefundRequest>,
+    pub insurance: Option<InsuranceCover>,
+    /// Bond the provider put up to take the job, returned on success and forfeited to
+    /// the requester on failure.
+    pub provider_stake: u128,
+    /// Paid on the provider's credit line to the requester rather than from escrow.
+    /// The provider bills it with an invoice once it completes.
+    pub on_credit: bool,
+}
+
+/// What a requester asks for and pays when opening a service request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceTerms {
+    pub service_type: String,
+    pub parameters: String,
+    pub asset: String,
+    pub payment: u128,
+    /// Optional stages with their own payments, which must add up to `payment`.
+    pub milestones: Vec<MilestoneTerms>,
+    /// Whether to buy cover from the insurance pool against the provider failing.
+    pub insured: bool,
+}
+
+impl ServiceRequest {
+    /// Moves `amount` out of escrow into what has been released to the provider.
+    pub fn release_from_escrow(&mut self, amount: u128) -> Result<(), AgentChainError> {
+        if self.escrow < amount {
+            return Err(AgentChainError::InsufficientBalance {
+                required: amount,
+                available: self.escrow,
+            });
+        }
+        self.escrow -= amount;
+        self.released += amount;
+        Ok(())
+    }
+
+    /// Empties the escrow and returns what it held, to be given back to the requester.
+    pub fn take_escrow(&mut self) -> u128 {
+        std::mem::take(&mut self.escrow)
+    }
+
+    /// Amount released to the provider that has not been refunded yet.
+    pub fn refundable(&self) -> u128 {
+        self.released - self.refunded
+    }
+
+    /// Checks that `amount` is a positive part of what is still refundable.
+    pub fn check_refund(&self, amount: u128) -> Result<(), AgentChainError> {
+        let refundable = self.refundable();
+        if amount == 0 || amount > refundable {
+            return Err(AgentChainError::ServiceRequestFailed(format!(
+                "Refund of {} exceeds refundable amount {}",
+                amount, refundable
+            )));
+        }
+        Ok(())
+    }
+
+    /// Splits a refund of `amount` across the payouts in proportion to what each still
+    /// holds. Rounding leftovers go to the earliest payouts that can cover them.
+    pub fn refund_shares(&self, amount: u128) -> Vec<u128> {
+        let outstanding = self.refundable();
+        let mut shares = self
+            .payouts
+            .iter()
+            .map(|payout| {
+                mul_div(amount, payout.outstanding(), outstanding).unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        let mut leftover = amount.saturating_sub(shares.iter().sum());
+        for (share, payout) in shares.iter_mut().zip(&self.payouts) {
+            let extra = leftover.min(payout.outstanding() - *share);
+            *share += extra;
+            leftover -= extra;
+        }
+        shares
+    }
+
+    /// Index of the first milestone that has not been accepted yet.
+    pub fn current_milestone(&self) -> Option<usize> {
+        self.milestones
+            .iter()
+            .position(|milestone| milestone.status != MilestoneStatus::Accepted)
+    }
+
+    pub fn milestones_accepted(&self) -> usize {
+        self.milestones
+            .iter()
+            .filter(|milestone| milestone.status == MilestoneStatus::Accepted)
+            .count()
+    }
+}
+
+/// One leg of a request's payment, as received by the provider or a beneficiary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Payout {
+    pub agent_id: String,
+    pub amount: u128,
+    pub refunded: u128,
+    pub transaction_id: String,
+}
+
+impl Payout {
+    pub fn outstanding(&self) -> u128 {
+        self.amount - self.refunded
+    }
+}
+
+/// A partial refund the requester has asked for and the provider has yet to answer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefundRequest {
+    pub amount: u128,
+    pub reason: String,
+    pub requested_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MilestoneTerms {
+    pub description: String,
+    pub payment: u128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Milestone {
+    pub description: String,
+    pub payment: u128,
+    pub status: MilestoneStatus,
+    pub submitted_at: Option<u64>,
+    pub accepted_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MilestoneStatus {
+    Pending,
+    Submitted,
+    Accepted,
+    Rejected,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -67,43 +411,304 @@ pub enum ServiceStatus {
     Disputed,
 }
 
+impl ServiceStatus {
+    /// Whether the request has been completed or failed and can no longer change.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, ServiceStatus::Completed | ServiceStatus::Failed)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: String,
     pub from_agent: String,
     pub to_agent: String,
+    pub asset: String,
     pub amount: u128,
     pub transaction_type: TransactionType,
     pub timestamp: u64,
+    pub request_id: Option<String>,
+    /// Transaction this one adjusts, e.g. the payment a refund returns.
+    pub related_transaction: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TransactionType {
     ServicePayment,
     Transfer,
+    DelegatedTransfer,
+    Refund,
+    Withdrawal,
+    InvoiceSettlement,
+    InsurancePremium,
+    InsurancePayout,
     Reward,
     Penalty,
+    OrderFill,
+    TreasuryDeposit,
+    TreasurySpend,
+    Deposit,
+}
+
+/// Application-wide settings, provided when the application is instantiated.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentChainConfig {
+    /// Minimum number of seconds between two withdrawals by the same agent.
+    pub withdrawal_cooldown: u64,
+    /// Fungible token applications that back non-native assets, by asset id.
+    pub fungible_applications: BTreeMap<String, ApplicationId>,
+    #[serde(default)]
+    pub lending: LendingConfig,
+    #[serde(default)]
+    pub insurance: InsuranceConfig,
+    #[serde(default)]
+    pub strategy_bounds: StrategyBounds,
+    #[serde(default)]
+    pub oracle: OracleConfig,
+    #[serde(default)]
+    pub governance: GovernanceConfig,
+    /// Owner allowed to resolve disputed requests. Disputes cannot be opened without one.
+    #[serde(default)]
+    pub dispute_arbiter: Option<String>,
+}
+
+/// Guardrails an owner sets on how fast an agent can spend its balances.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpendingPolicy {
+    /// Limits per asset id; assets without an entry are unrestricted.
+    pub limits: BTreeMap<String, SpendingLimits>,
+    /// Service types the agent may request. An empty list allows every type.
+    pub allowed_service_types: Vec<String>,
+    /// Length of a budget period in seconds, one day when zero.
+    pub period_seconds: u64,
+}
+
+impl SpendingPolicy {
+    pub const DEFAULT_PERIOD_SECONDS: u64 = 86_400;
+
+    pub fn period_seconds(&self) -> u64 {
+        if self.period_seconds == 0 {
+            Self::DEFAULT_PERIOD_SECONDS
+        } else {
+            self.period_seconds
+        }
+    }
+
+    pub fn allows_service_type(&self, service_type: &str) -> bool {
+        self.allowed_service_types.is_empty()
+            || self.allowed_service_types.iter().any(|allowed| allowed == service_type)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpendingLimits {
+    pub max_per_transaction: Option<u128>,
+    pub max_per_period: Option<u128>,
+    /// Maximum sent to any single counterparty within one budget period.
+    pub max_per_counterparty: Option<u128>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SpendingLimitKind {
+    PerTransaction,
+    PerPeriod,
+    PerCounterparty,
+}
+
+/// What an agent has spent of one asset in the current budget period.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpendingWindow {
+    pub period_start: u64,
+    pub spent: u128,
+    pub spent_by_counterparty: BTreeMap<String, u128>,
+}
+
+impl SpendingWindow {
+    /// Starts a new budget period once `period_seconds` have passed since this one began.
+    pub fn roll_over(&mut self, now: u64, period_seconds: u64) {
+        if now >= self.period_start.saturating_add(period_seconds) {
+            *self = SpendingWindow {
+                period_start: now,
+                ..SpendingWindow::default()
+            };
+        }
+    }
+
+    /// Checks a payment of `amount` to `counterparty` against `limits` and adds it to the
+    /// window if it fits.
+    pub fn record(
+        &mut self,
+        limits: &SpendingLimits,
+        counterparty: &str,
+        amount: u128,
+    ) -> Result<(), AgentChainError> {
+        if let Some(max) = limits.max_per_transaction {
+            if amount > max {
+                return Err(AgentChainError::SpendingLimitExceeded {
+                    kind: SpendingLimitKind::PerTransaction,
+                    allowed: max,
+                    attempted: amount,
+                });
+            }
+        }
+
+        let spent = self.spent.saturating_add(amount);
+        if let Some(max) = limits.max_per_period {
+            if spent > max {
+                return Err(AgentChainError::SpendingLimitExceeded {
+                    kind: SpendingLimitKind::PerPeriod,
+                    allowed: max,
+                    attempted: spent,
+                });
+            }
+        }
+
+        let spent_with_counterparty = self
+            .spent_by_counterparty
+            .get(counterparty)
+            .copied()
+            .unwrap_or(0)
+            .saturating_add(amount);
+        if let Some(max) = limits.max_per_counterparty {
+            if spent_with_counterparty > max {
+                return Err(AgentChainError::SpendingLimitExceeded {
+                    kind: SpendingLimitKind::PerCounterparty,
+                    allowed: max,
+                    attempted: spent_with_counterparty,
+                });
+            }
+        }
+
+        self.spent = spent;
+        self.spent_by_counterparty
+            .insert(counterparty.to_string(), spent_with_counterparty);
+        Ok(())
+    }
+}
+
+/// Amount of `asset` that `spender_agent` may move out of `owner_agent`'s balance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Allowance {
+    pub owner_agent: String,
+    pub spender_agent: String,
+    pub asset: String,
+    pub amount: u128,
+    pub expires_at: Option<u64>,
+    pub updated_at: u64,
+}
+
+impl Allowance {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketListing {
     pub agent_id: String,
     pub service_type: String,
+    pub asset: String,
     pub price: u128,
     pub capacity: u32,
     pub average_completion_time: u64,
     pub success_rate: f64,
+    pub status: ListingStatus,
+    /// Timestamp after which the listing is no longer offered.
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ListingStatus {
+    Active,
+    Paused,
+}
+
+impl MarketListing {
+    /// Whether the listing is offered to requesters at `now`.
+    pub fn is_available(&self, now: u64) -> bool {
+        self.status == ListingStatus::Active && self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+/// Outcomes of the closed requests an agent served for one service type, from which
+/// the stats of its market listing are derived.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServiceStats {
+    pub completed: u64,
+    pub failed: u64,
+    /// Sum of `completed_at - created_at` over completed requests.
+    pub total_completion_time: u64,
+}
+
+impl ServiceStats {
+    /// Share of closed requests that completed, in percent. Zero before any request
+    /// has closed.
+    pub fn success_rate(&self) -> f64 {
+        let total = self.completed + self.failed;
+        if total == 0 {
+            0.0
+        } else {
+            self.completed as f64 * 100.0 / total as f64
+        }
+    }
+
+    pub fn average_completion_time(&self) -> u64 {
+        if self.completed == 0 {
+            0
+        } else {
+            self.total_completion_time / self.completed
+        }
+    }
 }
 
 #[derive(RootView)]
 pub struct AgentChainState<C> {
+    pub config: RegisterView<C, AgentChainConfig>,
     pub agents: MapView<C, String, Agent>,
     pub service_requests: MapView<C, String, ServiceRequest>,
     pub transactions: MapView<C, String, Transaction>,
     pub market_listings: MapView<C, String, MarketListing>,
+    /// Every version of each agent's strategies, oldest first.
+    pub strategy_history: MapView<C, (String, u64), StrategyChange>,
+    /// Open requests per listing id, counted against `MarketListing::capacity`.
+    pub in_flight: MapView<C, String, u32>,
+    /// Closed request outcomes per listing id.
+    pub service_stats: MapView<C, String, ServiceStats>,
+    pub allowances: MapView<C, String, Allowance>,
+    pub spending_policies: MapView<C, String, SpendingPolicy>,
+    pub spending_windows: MapView<C, String, SpendingWindow>,
+    pub credit_lines: MapView<C, String, CreditLine>,
+    pub invoices: MapView<C, String, Invoice>,
+    /// Invoice issued for each invoiced request.
+    pub request_invoices: MapView<C, String, String>,
+    pub total_invoices: RegisterView<C, u64>,
+    pub lending_pools: MapView<C, String, LendingPool>,
+    pub lending_positions: MapView<C, String, LendingPosition>,
+    pub insurance_pools: MapView<C, String, InsurancePool>,
+    pub rfqs: MapView<C, String, Rfq>,
+    pub total_rfqs: RegisterView<C, u64>,
+    pub open_jobs: MapView<C, String, OpenJob>,
+    pub total_jobs: RegisterView<C, u64>,
+    pub orders: MapView<C, String, Order>,
+    /// Resting orders per asset pair, keyed by `base/quote`.
+    pub order_books: MapView<C, String, OrderBook>,
+    pub total_orders: RegisterView<C, u64>,
+    /// Amount committed to each agent's open orders, keyed by `agent_asset`.
+    pub order_exposure: MapView<C, String, u128>,
+    pub amm_pools: MapView<C, String, AmmPool>,
+    /// LP shares per pool and agent.
+    pub lp_shares: MapView<C, String, u128>,
+    pub data_feeds: MapView<C, String, DataFeed>,
+    pub proposals: MapView<C, String, Proposal>,
+    pub total_proposals: RegisterView<C, u64>,
+    /// Delegate of each Governance agent that delegated its votes.
+    pub delegations: MapView<C, String, String>,
+    /// Treasury balance per asset, spent by governance proposals.
+    pub treasury: MapView<C, String, u128>,
     pub total_agents: RegisterView<C, u64>,
+    pub total_requests: RegisterView<C, u64>,
     pub total_transactions: RegisterView<C, u64>,
-    pub total_volume: RegisterView<C, u128>,
+    pub total_volume: MapView<C, String, u128>,
 }
 
 impl<C: ViewStorageContext> AgentChainState<C> {
@@ -113,22 +718,36 @@ impl<C: ViewStorageContext> AgentChainState<C> {
         owner: String,
         name: String,
         description: String,
-        strategy: AgentStrategy,
-        initial_balance: u128,
+        strategies: Vec<StrategySlot>,
+        now: u64,
     ) -> Result<(), AgentChainError> {
+        self.validate_strategies(&strategies)?;
+
+        let change = StrategyChange {
+            version: 0,
+            changed_at: now,
+            signer: owner.clone(),
+            strategies: strategies.clone(),
+            rolled_back_to: None,
+        };
+        self.strategy_history.insert(&(id.clone(), 0), change)?;
+
         let agent = Agent {
             id: id.clone(),
             owner,
             name,
             description,
-            strategy,
-            balance: initial_balance,
+            strategies,
+            strategy_version: 0,
+            balances: BTreeMap::new(),
             reputation: 100,
             services_completed: 0,
             services_failed: 0,
-            created_at: Self::current_timestamp(),
-            last_active: Self::current_timestamp(),
+            created_at: now,
+            last_active: now,
             is_active: true,
+            last_withdrawal: None,
+            revenue_split: None,
         };
 
         self.agents.insert(&id, agent)?;
@@ -140,6 +759,148 @@ impl<C: ViewStorageContext> AgentChainState<C> {
         Ok(())
     }
 
+    /// Replaces the agent's slot for `strategy`'s kind, or adds one. Allocation and
+    /// enablement keep their current values when not given; a new slot starts enabled
+    /// with no allocation.
+    pub async fn update_strategy(
+        &mut self,
+        agent_id: &str,
+        signer: &str,
+        strategy: AgentStrategy,
+        allocation_bps: Option<u16>,
+        enabled: Option<bool>,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let agent = self.get_agent(agent_id).await?;
+        let mut slots = agent.strategies.clone();
+        match slots.iter_mut().find(|slot| slot.strategy.kind() == strategy.kind()) {
+            Some(slot) => {
+                slot.strategy = strategy;
+                slot.allocation_bps = allocation_bps.unwrap_or(slot.allocation_bps);
+                slot.enabled = enabled.unwrap_or(slot.enabled);
+            }
+            None => slots.push(StrategySlot {
+                strategy,
+                allocation_bps: allocation_bps.unwrap_or(0),
+                enabled: enabled.unwrap_or(true),
+            }),
+        }
+        self.validate_strategies(&slots)?;
+        self.change_strategies(agent, signer, slots, None, now).await
+    }
+
+    /// Turns one of the agent's strategies on or off without changing its settings.
+    pub async fn set_strategy_enabled(
+        &mut self,
+        agent_id: &str,
+        signer: &str,
+        strategy_type: &str,
+        enabled: bool,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let agent = self.get_agent(agent_id).await?;
+        let mut slots = agent.strategies.clone();
+        let slot = slots
+            .iter_mut()
+            .find(|slot| slot.strategy.kind() == strategy_type)
+            .ok_or_else(|| AgentChainError::InvalidStrategy {
+                field: "strategy_type".to_string(),
+                reason: format!("agent has no {} strategy", strategy_type),
+            })?;
+        slot.enabled = enabled;
+        self.change_strategies(agent, signer, slots, None, now).await
+    }
+
+    /// One version of the agent's strategies.
+    pub async fn get_strategy_change(
+        &self,
+        agent_id: &str,
+        version: u64,
+    ) -> Result<Option<StrategyChange>, AgentChainError> {
+        Ok(self
+            .strategy_history
+            .get(&(agent_id.to_string(), version))
+            .await?)
+    }
+
+    /// Every version of the agent's strategies, oldest first.
+    pub async fn get_strategy_history(
+        &self,
+        agent_id: &str,
+    ) -> Result<Vec<StrategyChange>, AgentChainError> {
+        let agent = self.get_agent(agent_id).await?;
+        let mut history = Vec::new();
+        for version in 0..=agent.strategy_version {
+            if let Some(change) = self.get_strategy_change(agent_id, version).await? {
+                history.push(change);
+            }
+        }
+        Ok(history)
+    }
+
+    /// Restores the strategies the agent had at `to_version`. The rollback is recorded
+    /// as a new version.
+    pub async fn rollback_strategy(
+        &mut self,
+        agent_id: &str,
+        signer: &str,
+        to_version: u64,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let agent = self.get_agent(agent_id).await?;
+        if to_version == agent.strategy_version {
+            return Err(AgentChainError::InvalidStrategy {
+                field: "to_version".to_string(),
+                reason: format!("version {} is already current", to_version),
+            });
+        }
+        let strategies = self
+            .get_strategy_change(agent_id, to_version)
+            .await?
+            .map(|change| change.strategies)
+            .ok_or_else(|| AgentChainError::InvalidStrategy {
+                field: "to_version".to_string(),
+                reason: format!("no version {} in the agent's history", to_version),
+            })?;
+        // Bounds may have changed since that version was current.
+        self.validate_strategies(&strategies)?;
+        self.change_strategies(agent, signer, strategies, Some(to_version), now).await
+    }
+
+    /// Replaces the agent's strategies and records them as a new version. Fails if
+    /// nothing would change.
+    async fn change_strategies(
+        &mut self,
+        mut agent: Agent,
+        signer: &str,
+        strategies: Vec<StrategySlot>,
+        rolled_back_to: Option<u64>,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        if strategies == agent.strategies {
+            return Err(AgentChainError::InvalidStrategy {
+                field: "strategies".to_string(),
+                reason: "no change from the current version".to_string(),
+            });
+        }
+        agent.strategies = strategies.clone();
+        agent.strategy_version += 1;
+        agent.last_active = now;
+        let change = StrategyChange {
+            version: agent.strategy_version,
+            changed_at: now,
+            signer: signer.to_string(),
+            strategies,
+            rolled_back_to,
+        };
+
+        let agent_id = agent.id.clone();
+        self.strategy_history
+            .insert(&(agent_id.clone(), change.version), change)?;
+        self.agents.insert(&agent_id, agent)?;
+        Ok(())
+    }
+
     pub async fn get_agent(&self, agent_id: &str) -> Result<Agent, AgentChainError> {
         self.agents
             .get(agent_id)
@@ -147,126 +908,1428 @@ impl<C: ViewStorageContext> AgentChainState<C> {
             .ok_or_else(|| AgentChainError::AgentNotFound(agent_id.to_string()))
     }
 
+    /// Loads an agent and checks that `owner` is the account that controls it.
+    pub async fn get_owned_agent(&self, agent_id: &str, owner: &str) -> Result<Agent, AgentChainError> {
+        let agent = self.get_agent(agent_id).await?;
+        if agent.owner != owner {
+            return Err(AgentChainError::Unauthorized);
+        }
+        Ok(agent)
+    }
+
     pub async fn transfer_tokens(
         &mut self,
         from_agent_id: &str,
         to_agent_id: &str,
+        asset: &str,
         amount: u128,
         transaction_type: TransactionType,
+        now: u64,
     ) -> Result<String, AgentChainError> {
         let mut from_agent = self.get_agent(from_agent_id).await?;
         let mut to_agent = self.get_agent(to_agent_id).await?;
 
-        if from_agent.balance < amount {
-            return Err(AgentChainError::InsufficientBalance {
-                required: amount,
-                available: from_agent.balance,
-            });
-        }
-
-        from_agent.balance -= amount;
-        to_agent.balance += amount;
+        self.record_spending(from_agent_id, to_agent_id, asset, amount, now).await?;
+        from_agent.debit(asset, amount)?;
+        to_agent.credit(asset, amount);
 
         self.agents.insert(from_agent_id, from_agent.clone())?;
         self.agents.insert(to_agent_id, to_agent.clone())?;
 
-        let transaction_id = format!("tx_{}", Self::current_timestamp());
+        self.record_transaction(from_agent_id, to_agent_id, asset, amount, transaction_type, now).await
+    }
+
+    pub(crate) async fn record_transaction(
+        &mut self,
+        from_agent_id: &str,
+        to_agent_id: &str,
+        asset: &str,
+        amount: u128,
+        transaction_type: TransactionType,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        self.record_linked_transaction(
+            from_agent_id,
+            to_agent_id,
+            asset,
+            amount,
+            transaction_type,
+            None,
+            None,
+            now,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn record_linked_transaction(
+        &mut self,
+        from_agent_id: &str,
+        to_agent_id: &str,
+        asset: &str,
+        amount: u128,
+        transaction_type: TransactionType,
+        request_id: Option<&str>,
+        related_transaction: Option<String>,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let mut total_txs = *self.total_transactions.get();
+        let transaction_id = format!("tx_{}_{}", now, total_txs);
         let transaction = Transaction {
             id: transaction_id.clone(),
             from_agent: from_agent_id.to_string(),
             to_agent: to_agent_id.to_string(),
+            asset: asset.to_string(),
             amount,
             transaction_type,
-            timestamp: Self::current_timestamp(),
+            timestamp: now,
+            request_id: request_id.map(str::to_string),
+            related_transaction,
         };
 
         self.transactions.insert(&transaction_id, transaction)?;
 
-        let mut total_txs = self.total_transactions.get();
-        *total_txs += 1;
+        total_txs += 1;
         self.total_transactions.set(total_txs);
 
-        let mut total_vol = self.total_volume.get();
-        *total_vol += amount;
-        self.total_volume.set(total_vol);
+        let total_vol = self.total_volume.get(asset).await?.unwrap_or(0);
+        self.total_volume.insert(asset, total_vol + amount)?;
 
         Ok(transaction_id)
     }
 
+    /// Creates a service request and moves its payment from the requester into escrow.
+    ///
+    /// When the terms have milestones, escrow is released one milestone at a time as the
+    /// requester accepts them. Insured requests also charge the requester a premium.
+    /// Inactive providers and paused or expired listings take no new requests.
     pub async fn create_service_request(
         &mut self,
         requester_agent: String,
         provider_agent: String,
-        service_type: String,
-        parameters: String,
-        payment: u128,
+        terms: ServiceTerms,
+        now: u64,
     ) -> Result<String, AgentChainError> {
-        let request_id = format!("req_{}", Self::current_timestamp());
-        
-        let request = ServiceRequest {
-            id: request_id.clone(),
-            requester_agent,
-            provider_agent,
-            service_type,
-            parameters,
-            payment,
-            status: ServiceStatus::Pending,
-            created_at: Self::current_timestamp(),
-            completed_at: None,
-        };
+        self.check_provider_available(&provider_agent, &terms.service_type, now)
+            .await?;
+        self.charge_requester(
+            &requester_agent,
+            &provider_agent,
+            &terms.service_type,
+            &terms.asset,
+            terms.payment,
+            now,
+        )
+        .await?;
+        self.open_service_request(requester_agent, provider_agent, terms, now).await
+    }
 
-        self.service_requests.insert(&request_id, request)?;
+    /// Checks a payment for a service against the requester's spending policy and takes
+    /// it from the requester's balance.
+    pub(crate) async fn charge_requester(
+        &mut self,
+        requester_agent: &str,
+        counterparty: &str,
+        service_type: &str,
+        asset: &str,
+        payment: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        self.check_service_type_allowed(requester_agent, service_type).await?;
+        self.record_spending(requester_agent, counterparty, asset, payment, now).await?;
+        let mut requester = self.get_agent(requester_agent).await?;
+        requester.debit(asset, payment)?;
+        self.agents.insert(requester_agent, requester)?;
+        Ok(())
+    }
+
+    /// Checks that the requester's spending policy lets it pay for `service_type`.
+    pub(crate) async fn check_service_type_allowed(
+        &self,
+        requester_agent: &str,
+        service_type: &str,
+    ) -> Result<(), AgentChainError> {
+        if let Some(policy) = self.spending_policies.get(requester_agent).await? {
+            if !policy.allows_service_type(service_type) {
+                return Err(AgentChainError::ServiceTypeNotAllowed(service_type.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a service request whose payment has already been taken from the
+    /// requester, and holds it in escrow.
+    pub(crate) async fn open_service_request(
+        &mut self,
+        requester_agent: String,
+        provider_agent: String,
+        terms: ServiceTerms,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let ServiceTerms {
+            service_type,
+            parameters,
+            asset,
+            payment,
+            milestones,
+            insured,
+        } = terms;
+
+        if !milestones.is_empty() {
+            let milestone_total: u128 = milestones.iter().map(|terms| terms.payment).sum();
+            if milestone_total != payment {
+                return Err(AgentChainError::ServiceRequestFailed(format!(
+                    "Milestone payments add up to {}, expected {}",
+                    milestone_total, payment
+                )));
+            }
+        }
+
+        let provider = self.get_agent(&provider_agent).await?;
+        let mut requester = self.get_agent(&requester_agent).await?;
+
+        self.reserve_capacity(&provider_agent, &service_type).await?;
+        let insurance = if insured {
+            Some(self.underwrite(&mut requester, &provider, &asset, payment, now).await?)
+        } else {
+            None
+        };
+        self.agents.insert(&requester_agent, requester)?;
+
+        let mut total_requests = *self.total_requests.get();
+        let request_id = format!("req_{}_{}", now, total_requests);
+        total_requests += 1;
+        self.total_requests.set(total_requests);
+
+        let request = ServiceRequest {
+            id: request_id.clone(),
+            requester_agent,
+            provider_agent,
+            service_type,
+            parameters,
+            asset,
+            payment,
+            status: ServiceStatus::Pending,
+            created_at: now,
+            completed_at: None,
+            milestones: milestones
+                .into_iter()
+                .map(|terms| Milestone {
+                    description: terms.description,
+                    payment: terms.payment,
+                    status: MilestoneStatus::Pending,
+                    submitted_at: None,
+                    accepted_at: None,
+                })
+                .collect(),
+            escrow: payment,
+            released: 0,
+            refunded: 0,
+            payouts: Vec::new(),
+            pending_refund: None,
+            insurance,
+            provider_stake: 0,
+            on_credit: false,
+        };
+
+        self.service_requests.insert(&request_id, request)?;
         Ok(request_id)
     }
 
+    pub async fn get_service_request(&self, request_id: &str) -> Result<ServiceRequest, AgentChainError> {
+        self.service_requests
+            .get(request_id)
+            .await?
+            .ok_or_else(|| AgentChainError::ServiceRequestFailed("Request not found".to_string()))
+    }
+
+    /// Closes an open request. On success the rest of the escrow is released to the
+    /// provider, on failure it goes back to the requester.
+    ///
+    /// Callers check that the signer may close the request this way; a request that is
+    /// already closed cannot be closed again, and a disputed one is closed by
+    /// `resolve_dispute`.
     pub async fn complete_service(
         &mut self,
         request_id: &str,
         success: bool,
+        now: u64,
     ) -> Result<(), AgentChainError> {
-        let mut request = self.service_requests
-            .get(request_id)
-            .await?
-            .ok_or_else(|| AgentChainError::ServiceRequestFailed("Request not found".to_string()))?;
+        let mut request = self.get_service_request(request_id).await?;
+        if request.status == ServiceStatus::Disputed {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Request is under dispute".to_string(),
+            ));
+        }
+        self.close_request(&mut request, success, false, now).await?;
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    /// Closes a disputed request with the arbiter's ruling. If the requester wins, the
+    /// escrow goes back to it and an insured requester is compensated for what was
+    /// already paid out; otherwise the request completes as if the requester had
+    /// accepted it.
+    pub async fn resolve_dispute(
+        &mut self,
+        request_id: &str,
+        requester_wins: bool,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        if request.status != ServiceStatus::Disputed {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Request is not under dispute".to_string(),
+            ));
+        }
+        self.close_request(&mut request, !requester_wins, requester_wins, now).await?;
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    /// Moves an open request to `Completed` or `Failed`, settling escrow, insurance and
+    /// stake and freeing the provider's capacity. An insured requester is compensated
+    /// whenever the request fails, and a failed credit-backed request gives its credit
+    /// back.
+    ///
+    /// This is the only way a request closes, so each request releases its capacity
+    /// slot exactly once.
+    async fn close_request(
+        &mut self,
+        request: &mut ServiceRequest,
+        success: bool,
+        dispute_won: bool,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        if request.status.is_closed() {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Request is already closed".to_string(),
+            ));
+        }
+        request.completed_at = Some(now);
 
         if success {
             request.status = ServiceStatus::Completed;
-            self.transfer_tokens(
+            for milestone in &mut request.milestones {
+                if milestone.status != MilestoneStatus::Accepted {
+                    milestone.status = MilestoneStatus::Accepted;
+                    milestone.accepted_at = Some(now);
+                }
+            }
+            let remaining = request.escrow;
+            self.release_escrow(request, remaining, now).await?;
+            self.settle_insurance(request, None, now).await?;
+            self.settle_provider_stake(request, true, now).await?;
+            self.record_service_outcome(request, true, now).await?;
+        } else {
+            self.refund_escrow(request).await?;
+            if request.on_credit {
+                self.release_credit(request, now).await?;
+            }
+            let claim = if dispute_won {
+                ClaimReason::DisputeLost
+            } else {
+                ClaimReason::ServiceFailed
+            };
+            self.settle_insurance(request, Some(claim), now).await?;
+            self.settle_provider_stake(request, false, now).await?;
+            request.status = ServiceStatus::Failed;
+            self.record_service_outcome(request, false, now).await?;
+        }
+        self.release_capacity(&request.provider_agent, &request.service_type).await
+    }
+
+    /// Flags an open request as disputed by either party, to be closed by the
+    /// configured arbiter with `resolve_dispute`.
+    pub async fn open_dispute(&mut self, request_id: &str) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        if request.status.is_closed() {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Request is already closed".to_string(),
+            ));
+        }
+        if self.config.get().dispute_arbiter.is_none() {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "No dispute arbiter is configured".to_string(),
+            ));
+        }
+
+        request.status = ServiceStatus::Disputed;
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    /// Records that the provider has taken on a pending request.
+    pub async fn accept_service(&mut self, request_id: &str) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        if request.status != ServiceStatus::Pending {
+            return Err(AgentChainError::ServiceRequestFailed(format!(
+                "Request is {:?}, not pending",
+                request.status
+            )));
+        }
+        request.status = ServiceStatus::Accepted;
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    /// Marks the current milestone of a request as delivered by the provider.
+    pub async fn submit_milestone(
+        &mut self,
+        request_id: &str,
+        milestone_index: usize,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        Self::check_current_milestone(&request, milestone_index)?;
+
+        let milestone = &mut request.milestones[milestone_index];
+        if milestone.status == MilestoneStatus::Submitted {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Milestone already submitted".to_string(),
+            ));
+        }
+        milestone.status = MilestoneStatus::Submitted;
+        milestone.submitted_at = Some(now);
+        request.status = ServiceStatus::InProgress;
+
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    /// Accepts a submitted milestone and releases its payment from escrow to the provider.
+    ///
+    /// Accepting the last milestone completes the request.
+    pub async fn accept_milestone(
+        &mut self,
+        request_id: &str,
+        milestone_index: usize,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        Self::check_current_milestone(&request, milestone_index)?;
+
+        if request.milestones[milestone_index].status != MilestoneStatus::Submitted {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Milestone has not been submitted".to_string(),
+            ));
+        }
+
+        let payment = request.milestones[milestone_index].payment;
+        self.release_escrow(&mut request, payment, now).await?;
+
+        let milestone = &mut request.milestones[milestone_index];
+        milestone.status = MilestoneStatus::Accepted;
+        milestone.accepted_at = Some(now);
+
+        if request.current_milestone().is_none() {
+            self.close_request(&mut request, true, false, now).await?;
+        }
+
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    /// Sends a submitted milestone back to the provider without releasing any payment.
+    pub async fn reject_milestone(
+        &mut self,
+        request_id: &str,
+        milestone_index: usize,
+    ) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        Self::check_current_milestone(&request, milestone_index)?;
+
+        let milestone = &mut request.milestones[milestone_index];
+        if milestone.status != MilestoneStatus::Submitted {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Milestone has not been submitted".to_string(),
+            ));
+        }
+        milestone.status = MilestoneStatus::Rejected;
+
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    fn check_current_milestone(
+        request: &ServiceRequest,
+        milestone_index: usize,
+    ) -> Result<(), AgentChainError> {
+        if request.status.is_closed() {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Request is already closed".to_string(),
+            ));
+        }
+        if request.status == ServiceStatus::Disputed {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "Request is under dispute".to_string(),
+            ));
+        }
+        match request.current_milestone() {
+            Some(current) if current == milestone_index => Ok(()),
+            Some(current) => Err(AgentChainError::ServiceRequestFailed(format!(
+                "Milestone {} must be completed before milestone {}",
+                current, milestone_index
+            ))),
+            None => Err(AgentChainError::ServiceRequestFailed(
+                "Request has no open milestones".to_string(),
+            )),
+        }
+    }
+
+    async fn release_escrow(
+        &mut self,
+        request: &mut ServiceRequest,
+        amount: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let provider = self.get_agent(&request.provider_agent).await?;
+        let legs = match &provider.revenue_split {
+            Some(split) => split.legs(&request.provider_agent, amount),
+            None => vec![(request.provider_agent.clone(), amount)],
+        };
+
+        request.release_from_escrow(amount)?;
+        for (beneficiary_id, share) in legs {
+            if share == 0 {
+                continue;
+            }
+            let mut beneficiary = self.get_agent(&beneficiary_id).await?;
+            beneficiary.credit(&request.asset, share);
+            self.agents.insert(&beneficiary_id, beneficiary)?;
+
+            let transaction_id = self.record_linked_transaction(
                 &request.requester_agent,
-                &request.provider_agent,
-                request.payment,
+                &beneficiary_id,
+                &request.asset,
+                share,
                 TransactionType::ServicePayment,
+                Some(&request.id),
+                None,
+                now,
+            ).await?;
+            request.payouts.push(Payout {
+                agent_id: beneficiary_id,
+                amount: share,
+                refunded: 0,
+                transaction_id,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns part or all of what has been paid out for a request back to the requester.
+    ///
+    /// Each recipient of the payment gives back its share of the refund, in proportion to
+    /// what it still holds from the request, and each leg is recorded against the payment
+    /// it reverses. Refunds are not subject to the recipients' spending policies.
+    pub async fn refund_payment(
+        &mut self,
+        request_id: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<Vec<String>, AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        request.check_refund(amount)?;
+
+        let shares = request.refund_shares(amount);
+        let mut transaction_ids = Vec::new();
+        for (payout, share) in request.payouts.iter_mut().zip(shares) {
+            if share == 0 {
+                continue;
+            }
+            let mut recipient = self.get_agent(&payout.agent_id).await?;
+            recipient.debit(&request.asset, share)?;
+            self.agents.insert(&payout.agent_id, recipient)?;
+            let mut requester = self.get_agent(&request.requester_agent).await?;
+            requester.credit(&request.asset, share);
+            self.agents.insert(&request.requester_agent, requester)?;
+
+            let transaction_id = self.record_linked_transaction(
+                &payout.agent_id,
+                &request.requester_agent,
+                &request.asset,
+                share,
+                TransactionType::Refund,
+                Some(request_id),
+                Some(payout.transaction_id.clone()),
+                now,
             ).await?;
+            payout.refunded += share;
+            transaction_ids.push(transaction_id);
+        }
 
+        request.refunded += amount;
+        if request
+            .pending_refund
+            .as_ref()
+            .is_some_and(|pending| pending.amount > request.refundable())
+        {
+            request.pending_refund = None;
+        }
+        self.service_requests.insert(request_id, request)?;
+
+        Ok(transaction_ids)
+    }
+
+    /// Records a requester's ask for a partial refund, to be approved by the provider.
+    pub async fn request_refund(
+        &mut self,
+        request_id: &str,
+        amount: u128,
+        reason: String,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        request.check_refund(amount)?;
+
+        request.pending_refund = Some(RefundRequest {
+            amount,
+            reason,
+            requested_at: now,
+        });
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    pub async fn approve_refund(
+        &mut self,
+        request_id: &str,
+        now: u64,
+    ) -> Result<Vec<String>, AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        let pending = request.pending_refund.take().ok_or_else(|| {
+            AgentChainError::ServiceRequestFailed("No refund has been requested".to_string())
+        })?;
+        self.service_requests.insert(request_id, request)?;
+
+        self.refund_payment(request_id, pending.amount, now).await
+    }
+
+    pub async fn reject_refund(&mut self, request_id: &str) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        if request.pending_refund.take().is_none() {
+            return Err(AgentChainError::ServiceRequestFailed(
+                "No refund has been requested".to_string(),
+            ));
+        }
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    async fn refund_escrow(&mut self, request: &mut ServiceRequest) -> Result<(), AgentChainError> {
+        let escrow = request.take_escrow();
+        if escrow == 0 {
+            return Ok(());
+        }
+
+        let mut requester = self.get_agent(&request.requester_agent).await?;
+        requester.credit(&request.asset, escrow);
+        self.agents.insert(&request.requester_agent, requester)?;
+        Ok(())
+    }
+
+    /// Takes `amount` from the provider's balance as a bond on the request.
+    pub(crate) async fn lock_provider_stake(
+        &mut self,
+        request_id: &str,
+        amount: u128,
+    ) -> Result<(), AgentChainError> {
+        let mut request = self.get_service_request(request_id).await?;
+        let mut provider = self.get_agent(&request.provider_agent).await?;
+        provider.debit(&request.asset, amount)?;
+        self.agents.insert(&request.provider_agent, provider)?;
+
+        request.provider_stake += amount;
+        self.service_requests.insert(request_id, request)?;
+        Ok(())
+    }
+
+    /// Returns the provider's bond on success and forfeits it to the requester on
+    /// failure. Requests only fail on the provider's own report or on a dispute ruled
+    /// for the requester, so those are the only ways to lose the stake.
+    async fn settle_provider_stake(
+        &mut self,
+        request: &mut ServiceRequest,
+        success: bool,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        if request.provider_stake == 0 {
+            return Ok(());
+        }
+
+        let stake = request.provider_stake;
+        request.provider_stake = 0;
+        if success {
             let mut provider = self.get_agent(&request.provider_agent).await?;
+            provider.credit(&request.asset, stake);
+            self.agents.insert(&request.provider_agent, provider)?;
+        } else {
+            let mut requester = self.get_agent(&request.requester_agent).await?;
+            requester.credit(&request.asset, stake);
+            self.agents.insert(&request.requester_agent, requester)?;
+            self.record_linked_transaction(
+                &request.provider_agent,
+                &request.requester_agent,
+                &request.asset,
+                stake,
+                TransactionType::Penalty,
+                Some(&request.id),
+                None,
+                now,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Updates the provider's reputation and the stats of its listing for the request's
+    /// service type once the request has closed.
+    async fn record_service_outcome(
+        &mut self,
+        request: &ServiceRequest,
+        success: bool,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        let provider_agent = &request.provider_agent;
+        let mut provider = self.get_agent(provider_agent).await?;
+        if success {
             provider.services_completed += 1;
             provider.reputation = std::cmp::min(provider.reputation + 1, 1000);
-            self.agents.insert(&request.provider_agent, provider)?;
         } else {
-            request.status = ServiceStatus::Failed;
-            
-            let mut provider = self.get_agent(&request.provider_agent).await?;
             provider.services_failed += 1;
             provider.reputation = provider.reputation.saturating_sub(5);
-            self.agents.insert(&request.provider_agent, provider)?;
         }
+        self.agents.insert(provider_agent, provider)?;
 
-        request.completed_at = Some(Self::current_timestamp());
-        self.service_requests.insert(request_id, request)?;
+        let listing_id = Self::listing_id(provider_agent, &request.service_type);
+        let mut stats = self.service_stats.get(&listing_id).await?.unwrap_or_default();
+        if success {
+            let completed_at = request.completed_at.unwrap_or(now);
+            stats.completed += 1;
+            stats.total_completion_time += completed_at.saturating_sub(request.created_at);
+        } else {
+            stats.failed += 1;
+        }
+        if let Some(mut listing) = self.market_listings.get(&listing_id).await? {
+            listing.success_rate = stats.success_rate();
+            listing.average_completion_time = stats.average_completion_time();
+            self.market_listings.insert(&listing_id, listing)?;
+        }
+        self.service_stats.insert(&listing_id, stats)?;
+        Ok(())
+    }
+
+    /// Credits `agent_id` with tokens its owner has already moved into the application.
+    pub async fn deposit(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        agent.credit(asset, amount);
+        let owner = agent.owner.clone();
+        self.agents.insert(agent_id, agent)?;
 
+        self.record_transaction(&owner, agent_id, asset, amount, TransactionType::Deposit, now)
+            .await
+    }
+
+    /// Takes `amount` of `asset` out of an agent's balance so that it can be paid out
+    /// to the owner's account.
+    ///
+    /// The caller is responsible for moving the tokens; this only updates the agent and
+    /// the ledger.
+    pub async fn withdraw(
+        &mut self,
+        agent_id: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+        let cooldown = self.config.get().withdrawal_cooldown;
+        if let Some(last_withdrawal) = agent.last_withdrawal {
+            let available_at = last_withdrawal.saturating_add(cooldown);
+            if now < available_at {
+                return Err(AgentChainError::WithdrawalCooldown { available_at });
+            }
+        }
+
+        agent.debit(asset, amount)?;
+        agent.last_withdrawal = Some(now);
+        let owner = agent.owner.clone();
+        self.agents.insert(agent_id, agent)?;
+
+        self.record_transaction(agent_id, &owner, asset, amount, TransactionType::Withdrawal, now)
+            .await
+    }
+
+    pub async fn set_revenue_split(
+        &mut self,
+        agent_id: &str,
+        split: Option<RevenueSplit>,
+    ) -> Result<(), AgentChainError> {
+        let mut agent = self.get_agent(agent_id).await?;
+
+        if let Some(split) = &split {
+            let mut total = split.owner_bps as u32;
+            for beneficiary in split.operator.iter().chain(split.referrer.iter()) {
+                if beneficiary.agent_id == agent_id {
+                    return Err(AgentChainError::InvalidRevenueSplit(
+                        "An agent cannot be its own operator or referrer".to_string(),
+                    ));
+                }
+                self.get_agent(&beneficiary.agent_id).await?;
+                total += beneficiary.bps as u32;
+            }
+            if total != RevenueSplit::TOTAL_BPS {
+                return Err(AgentChainError::InvalidRevenueSplit(format!(
+                    "Shares add up to {} basis points, expected {}",
+                    total,
+                    RevenueSplit::TOTAL_BPS
+                )));
+            }
+        }
+
+        agent.revenue_split = split;
+        self.agents.insert(agent_id, agent)?;
+        Ok(())
+    }
+
+    pub async fn set_spending_policy(
+        &mut self,
+        agent_id: &str,
+        policy: SpendingPolicy,
+    ) -> Result<(), AgentChainError> {
+        self.spending_policies.insert(agent_id, policy)?;
+        Ok(())
+    }
+
+    pub fn spending_window_key(agent_id: &str, asset: &str) -> String {
+        format!("{}_{}", agent_id, asset)
+    }
+
+    /// Checks an outgoing payment against the agent's spending policy and, if it fits,
+    /// adds it to the current budget period.
+    pub(crate) async fn record_spending(
+        &mut self,
+        agent_id: &str,
+        counterparty: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        if let Some(window) = self
+            .spending_window_after(agent_id, counterparty, asset, amount, now)
+            .await?
+        {
+            self.spending_windows
+                .insert(&Self::spending_window_key(agent_id, asset), window)?;
+        }
         Ok(())
     }
 
-    pub async fn update_market_listing(&mut self, listing: MarketListing) -> Result<(), AgentChainError> {
-        let listing_id = format!("{}_{}", listing.agent_id, listing.service_type);
+    /// Checks an outgoing payment against the agent's spending policy without recording
+    /// it.
+    pub(crate) async fn check_spending(
+        &self,
+        agent_id: &str,
+        counterparty: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        self.spending_window_after(agent_id, counterparty, asset, amount, now)
+            .await
+            .map(|_| ())
+    }
+
+    /// The agent's budget period for `asset` with the payment added, or `None` if its
+    /// policy does not limit `asset`.
+    async fn spending_window_after(
+        &self,
+        agent_id: &str,
+        counterparty: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<Option<SpendingWindow>, AgentChainError> {
+        let Some(policy) = self.spending_policies.get(agent_id).await? else {
+            return Ok(None);
+        };
+        let Some(limits) = policy.limits.get(asset) else {
+            return Ok(None);
+        };
+
+        let key = Self::spending_window_key(agent_id, asset);
+        let mut window = self.spending_windows.get(&key).await?.unwrap_or_default();
+        window.roll_over(now, policy.period_seconds());
+        window.record(limits, counterparty, amount)?;
+        Ok(Some(window))
+    }
+
+    pub fn allowance_key(owner_agent: &str, spender_agent: &str, asset: &str) -> String {
+        format!("{}_{}_{}", owner_agent, spender_agent, asset)
+    }
+
+    /// Sets the allowance of `spender_agent` over `owner_agent`'s `asset`, replacing any previous one.
+    pub async fn approve(
+        &mut self,
+        owner_agent: &str,
+        spender_agent: &str,
+        asset: &str,
+        amount: u128,
+        expires_at: Option<u64>,
+        now: u64,
+    ) -> Result<(), AgentChainError> {
+        self.get_agent(spender_agent).await?;
+
+        let allowance = Allowance {
+            owner_agent: owner_agent.to_string(),
+            spender_agent: spender_agent.to_string(),
+            asset: asset.to_string(),
+            amount,
+            expires_at,
+            updated_at: now,
+        };
+        self.allowances
+            .insert(&Self::allowance_key(owner_agent, spender_agent, asset), allowance)?;
+        Ok(())
+    }
+
+    pub async fn revoke_allowance(
+        &mut self,
+        owner_agent: &str,
+        spender_agent: &str,
+        asset: &str,
+    ) -> Result<(), AgentChainError> {
+        self.allowances
+            .remove(&Self::allowance_key(owner_agent, spender_agent, asset))?;
+        Ok(())
+    }
+
+    /// Moves `amount` of `asset` from `from_agent` to `to_agent` on behalf of `spender_agent`,
+    /// consuming the corresponding allowance.
+    pub async fn transfer_from(
+        &mut self,
+        spender_agent: &str,
+        from_agent: &str,
+        to_agent: &str,
+        asset: &str,
+        amount: u128,
+        now: u64,
+    ) -> Result<String, AgentChainError> {
+        let key = Self::allowance_key(from_agent, spender_agent, asset);
+        let mut allowance = self.allowances.get(&key).await?.ok_or(
+            AgentChainError::InsufficientAllowance {
+                required: amount,
+                available: 0,
+            },
+        )?;
+
+        if allowance.is_expired(now) {
+            self.allowances.remove(&key)?;
+            return Err(AgentChainError::AllowanceExpired(allowance.expires_at.unwrap_or(now)));
+        }
+        if allowance.amount < amount {
+            return Err(AgentChainError::InsufficientAllowance {
+                required: amount,
+                available: allowance.amount,
+            });
+        }
+
+        let transaction_id = self
+            .transfer_tokens(from_agent, to_agent, asset, amount, TransactionType::DelegatedTransfer, now)
+            .await?;
+
+        allowance.amount -= amount;
+        allowance.updated_at = now;
+        if allowance.amount == 0 {
+            self.allowances.remove(&key)?;
+        } else {
+            self.allowances.insert(&key, allowance)?;
+        }
+
+        Ok(transaction_id)
+    }
+
+    /// Publishes or updates `owner`'s listing. The success rate and average completion
+    /// time are taken from the agent's closed requests, not from `listing`.
+    pub async fn update_market_listing(
+        &mut self,
+        owner: &str,
+        mut listing: MarketListing,
+    ) -> Result<(), AgentChainError> {
+        self.get_owned_agent(&listing.agent_id, owner).await?;
+        let listing_id = Self::listing_id(&listing.agent_id, &listing.service_type);
+        let stats = self.service_stats.get(&listing_id).await?.unwrap_or_default();
+        listing.success_rate = stats.success_rate();
+        listing.average_completion_time = stats.average_completion_time();
         self.market_listings.insert(&listing_id, listing)?;
         Ok(())
     }
 
-    fn current_timestamp() -> u64 {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
+    pub async fn get_market_listing(
+        &self,
+        agent_id: &str,
+        service_type: &str,
+    ) -> Result<MarketListing, AgentChainError> {
+        self.market_listings
+            .get(&Self::listing_id(agent_id, service_type))
+            .await?
+            .ok_or_else(|| AgentChainError::ListingFailed("Listing not found".to_string()))
+    }
+
+    /// Pauses or resumes a listing.
+    pub async fn set_listing_status(
+        &mut self,
+        agent_id: &str,
+        service_type: &str,
+        status: ListingStatus,
+    ) -> Result<(), AgentChainError> {
+        let mut listing = self.get_market_listing(agent_id, service_type).await?;
+        if listing.status == status {
+            return Err(AgentChainError::ListingFailed(format!(
+                "Listing is already {:?}",
+                status
+            )));
+        }
+        listing.status = status;
+        self.market_listings
+            .insert(&Self::listing_id(agent_id, service_type), listing)?;
+        Ok(())
+    }
+
+    pub async fn delete_market_listing(
+        &mut self,
+        agent_id: &str,
+        service_type: &str,
+    ) -> Result<(), AgentChainError> {
+        self.get_market_listing(agent_id, service_type).await?;
+        self.market_listings
+            .remove(&Self::listing_id(agent_id, service_type))?;
+        Ok(())
+    }
+
+    /// Removes every listing of `agent_id`, returning how many were removed.
+    pub async fn delist_agent(&mut self, agent_id: &str) -> Result<usize, AgentChainError> {
+        let mut listing_ids = Vec::new();
+        self.market_listings
+            .for_each_index_value(|key, listing| {
+                if listing.agent_id == agent_id {
+                    listing_ids.push(key);
+                }
+                Ok(())
+            })
+            .await?;
+        for listing_id in &listing_ids {
+            self.market_listings.remove(listing_id)?;
+        }
+        Ok(listing_ids.len())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn request(payment: u128, milestone_payments: &[u128]) -> ServiceRequest {
+        ServiceRequest {
+            id: "req_1".to_string(),
+            requester_agent: "requester".to_string(),
+            provider_agent: "provider".to_string(),
+            service_type: "analysis".to_string(),
+            parameters: String::new(),
+            asset: NATIVE_ASSET.to_string(),
+            payment,
+            status: ServiceStatus::Pending,
+            created_at: 0,
+            completed_at: None,
+            milestones: milestone_payments
+                .iter()
+                .map(|payment| Milestone {
+                    description: String::new(),
+                    payment: *payment,
+                    status: MilestoneStatus::Pending,
+                    submitted_at: None,
+                    accepted_at: None,
+                })
+                .collect(),
+            escrow: payment,
+            released: 0,
+            refunded: 0,
+            payouts: Vec::new(),
+            pending_refund: None,
+            insurance: None,
+            provider_stake: 0,
+            on_credit: false,
+        }
+    }
+
+    pub(crate) fn agent(id: &str) -> Agent {
+        Agent {
+            id: id.to_string(),
+            owner: format!("owner_{}", id),
+            name: id.to_string(),
+            description: String::new(),
+            strategies: Vec::new(),
+            strategy_version: 0,
+            balances: BTreeMap::new(),
+            reputation: 100,
+            services_completed: 0,
+            services_failed: 0,
+            created_at: 0,
+            last_active: 0,
+            is_active: true,
+            last_withdrawal: None,
+            revenue_split: None,
+        }
+    }
+
+    #[test]
+    fn balances_are_kept_per_asset() {
+        let mut agent = agent("a");
+        agent.credit(NATIVE_ASSET, 50);
+        agent.credit("usdc", 20);
+        agent.credit("usdc", 5);
+        assert_eq!(agent.balance_of(NATIVE_ASSET), 50);
+        assert_eq!(agent.balance_of("usdc"), 25);
+        assert_eq!(agent.balance_of("eth"), 0);
+
+        agent.debit("usdc", 25).unwrap();
+        assert_eq!(agent.balance_of("usdc"), 0);
+        assert_eq!(agent.balance_of(NATIVE_ASSET), 50);
+    }
+
+    #[test]
+    fn debiting_more_than_the_asset_balance_fails() {
+        let mut agent = agent("a");
+        agent.credit(NATIVE_ASSET, 50);
+        agent.credit("usdc", 10);
+        let error = agent.debit("usdc", 11).unwrap_err();
+        assert!(matches!(
+            error,
+            AgentChainError::InsufficientBalance {
+                required: 11,
+                available: 10
+            }
+        ));
+        assert_eq!(agent.balance_of("usdc"), 10);
+    }
+
+    #[test]
+    fn releasing_escrow_moves_it_to_released() {
+        let mut request = request(100, &[]);
+        request.release_from_escrow(40).unwrap();
+        assert_eq!(request.escrow, 60);
+        assert_eq!(request.released, 40);
+        assert_eq!(request.refundable(), 40);
+    }
+
+    #[test]
+    fn releasing_more_than_escrow_fails_without_changes() {
+        let mut request = request(100, &[]);
+        request.release_from_escrow(70).unwrap();
+        let error = request.release_from_escrow(31).unwrap_err();
+        assert!(matches!(
+            error,
+            AgentChainError::InsufficientBalance {
+                required: 31,
+                available: 30
+            }
+        ));
+        assert_eq!(request.escrow, 30);
+        assert_eq!(request.released, 70);
+    }
+
+    #[test]
+    fn taking_escrow_empties_it_once() {
+        let mut request = request(100, &[]);
+        request.release_from_escrow(25).unwrap();
+        assert_eq!(request.take_escrow(), 75);
+        assert_eq!(request.take_escrow(), 0);
+        assert_eq!(request.released, 25);
+    }
+
+    #[test]
+    fn refunds_reduce_what_is_refundable() {
+        let mut request = request(100, &[]);
+        request.release_from_escrow(100).unwrap();
+        request.refunded = 30;
+        assert_eq!(request.refundable(), 70);
+    }
+
+    #[test]
+    fn refunds_must_be_positive_and_within_what_was_released() {
+        let mut request = request(100, &[]);
+        assert!(request.check_refund(1).is_err());
+
+        request.release_from_escrow(60).unwrap();
+        assert!(request.check_refund(0).is_err());
+        assert!(request.check_refund(61).is_err());
+        request.check_refund(60).unwrap();
+
+        request.refunded = 60;
+        assert!(request.check_refund(1).is_err());
+    }
+
+    #[test]
+    fn milestones_are_accepted_in_order() {
+        let mut request = request(100, &[30, 70]);
+        assert_eq!(request.current_milestone(), Some(0));
+        request.milestones[0].status = MilestoneStatus::Accepted;
+        assert_eq!(request.current_milestone(), Some(1));
+        assert_eq!(request.milestones_accepted(), 1);
+        request.milestones[1].status = MilestoneStatus::Accepted;
+        assert_eq!(request.current_milestone(), None);
+    }
+
+    #[test]
+    fn only_completed_and_failed_requests_are_closed() {
+        assert!(ServiceStatus::Completed.is_closed());
+        assert!(ServiceStatus::Failed.is_closed());
+        for status in [
+            ServiceStatus::Pending,
+            ServiceStatus::Accepted,
+            ServiceStatus::InProgress,
+            ServiceStatus::Disputed,
+        ] {
+            assert!(!status.is_closed());
+        }
+    }
+
+    #[test]
+    fn allowances_expire_at_their_deadline() {
+        let mut allowance = Allowance {
+            owner_agent: "a".to_string(),
+            spender_agent: "b".to_string(),
+            asset: "native".to_string(),
+            amount: 100,
+            expires_at: Some(1_000),
+            updated_at: 0,
+        };
+        assert!(!allowance.is_expired(999));
+        assert!(allowance.is_expired(1_000));
+
+        allowance.expires_at = None;
+        assert!(!allowance.is_expired(u64::MAX));
+    }
+
+    fn limits(
+        max_per_transaction: Option<u128>,
+        max_per_period: Option<u128>,
+        max_per_counterparty: Option<u128>,
+    ) -> SpendingLimits {
+        SpendingLimits {
+            max_per_transaction,
+            max_per_period,
+            max_per_counterparty,
+        }
+    }
+
+    fn exceeded(result: Result<(), AgentChainError>) -> SpendingLimitKind {
+        match result {
+            Err(AgentChainError::SpendingLimitExceeded { kind, .. }) => kind,
+            other => panic!("expected a spending limit error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn payments_over_the_per_transaction_limit_are_rejected() {
+        let mut window = SpendingWindow::default();
+        let limits = limits(Some(100), None, None);
+        assert_eq!(
+            exceeded(window.record(&limits, "b", 101)),
+            SpendingLimitKind::PerTransaction
+        );
+        window.record(&limits, "b", 100).unwrap();
+        assert_eq!(window.spent, 100);
+    }
+
+    #[test]
+    fn period_and_counterparty_limits_add_up_within_a_window() {
+        let mut window = SpendingWindow::default();
+        let limits = limits(None, Some(250), Some(150));
+        window.record(&limits, "b", 100).unwrap();
+        assert_eq!(
+            exceeded(window.record(&limits, "b", 60)),
+            SpendingLimitKind::PerCounterparty
+        );
+        window.record(&limits, "c", 150).unwrap();
+        assert_eq!(exceeded(window.record(&limits, "d", 1)), SpendingLimitKind::PerPeriod);
+
+        // Rejected payments leave the window untouched.
+        assert_eq!(window.spent, 250);
+        assert_eq!(window.spent_by_counterparty.get("b"), Some(&100));
+        assert_eq!(window.spent_by_counterparty.get("d"), None);
+    }
+
+    #[test]
+    fn windows_reset_after_the_period() {
+        let mut window = SpendingWindow::default();
+        window.roll_over(100_000, 86_400);
+        assert_eq!(window.period_start, 100_000);
+        window.record(&limits(None, Some(100), None), "b", 100).unwrap();
+
+        window.roll_over(186_399, 86_400);
+        assert_eq!(window.spent, 100);
+        window.roll_over(186_400, 86_400);
+        assert_eq!(window.period_start, 186_400);
+        assert_eq!(window.spent, 0);
+        assert!(window.spent_by_counterparty.is_empty());
+    }
+
+    #[test]
+    fn an_empty_allowlist_allows_every_service_type() {
+        let mut policy = SpendingPolicy::default();
+        assert!(policy.allows_service_type("audit"));
+        policy.allowed_service_types = vec!["audit".to_string()];
+        assert!(policy.allows_service_type("audit"));
+        assert!(!policy.allows_service_type("translation"));
+        assert_eq!(policy.period_seconds(), SpendingPolicy::DEFAULT_PERIOD_SECONDS);
+    }
+
+    fn payout(agent_id: &str, amount: u128, refunded: u128) -> Payout {
+        Payout {
+            agent_id: agent_id.to_string(),
+            amount,
+            refunded,
+            transaction_id: format!("tx_{}", agent_id),
+        }
+    }
+
+    #[test]
+    fn revenue_split_rounding_goes_to_the_owner() {
+        let split = RevenueSplit {
+            owner_bps: 7_000,
+            operator: Some(Beneficiary {
+                agent_id: "operator".to_string(),
+                bps: 2_000,
+            }),
+            referrer: Some(Beneficiary {
+                agent_id: "referrer".to_string(),
+                bps: 1_000,
+            }),
+        };
+        assert_eq!(
+            split.legs("owner", 1_005),
+            vec![
+                ("owner".to_string(), 704),
+                ("operator".to_string(), 201),
+                ("referrer".to_string(), 100),
+            ]
+        );
+
+        let legs = split.legs("owner", u128::MAX);
+        assert_eq!(legs.iter().map(|(_, share)| share).sum::<u128>(), u128::MAX);
+    }
+
+    #[test]
+    fn refunds_are_shared_by_every_payout_in_proportion() {
+        let mut request = request(1_000, &[]);
+        request.release_from_escrow(1_000).unwrap();
+        request.payouts = vec![
+            payout("owner", 700, 0),
+            payout("operator", 200, 0),
+            payout("referrer", 100, 0),
+        ];
+
+        assert_eq!(request.refund_shares(500), vec![350, 100, 50]);
+        assert_eq!(request.refund_shares(1_000), vec![700, 200, 100]);
+        // Rounding leftovers go to the first payout with room for them.
+        assert_eq!(request.refund_shares(7), vec![6, 1, 0]);
+    }
+
+    #[test]
+    fn refund_shares_follow_what_each_payout_still_holds() {
+        let mut request = request(1_000, &[]);
+        request.release_from_escrow(1_000).unwrap();
+        request.refunded = 700;
+        request.payouts = vec![payout("owner", 700, 700), payout("operator", 300, 0)];
+
+        assert_eq!(request.refund_shares(300), vec![0, 300]);
+    }
+
+    #[test]
+    fn service_stats_are_zero_before_any_request_closes() {
+        let stats = ServiceStats::default();
+        assert_eq!(stats.success_rate(), 0.0);
+        assert_eq!(stats.average_completion_time(), 0);
+    }
+
+    #[test]
+    fn service_stats_average_over_completed_requests_only() {
+        let stats = ServiceStats {
+            completed: 3,
+            failed: 1,
+            total_completion_time: 90,
+        };
+        assert_eq!(stats.success_rate(), 75.0);
+        assert_eq!(stats.average_completion_time(), 30);
+
+        let failures_only = ServiceStats {
+            completed: 0,
+            failed: 2,
+            total_completion_time: 0,
+        };
+        assert_eq!(failures_only.success_rate(), 0.0);
+        assert_eq!(failures_only.average_completion_time(), 0);
+    }
+
+    #[test]
+    fn listings_are_available_while_active_and_unexpired() {
+        let mut listing = MarketListing {
+            agent_id: "provider".to_string(),
+            service_type: "analysis".to_string(),
+            asset: NATIVE_ASSET.to_string(),
+            price: 10,
+            capacity: 1,
+            average_completion_time: 0,
+            success_rate: 0.0,
+            status: ListingStatus::Active,
+            expires_at: None,
+        };
+        assert!(listing.is_available(u64::MAX));
+
+        listing.expires_at = Some(1_000);
+        assert!(listing.is_available(999));
+        assert!(!listing.is_available(1_000));
+
+        listing.status = ListingStatus::Paused;
+        assert!(!listing.is_available(0));
+    }
+
+    #[test]
+    fn only_enabled_slots_are_run() {
+        let mut agent = agent("a");
+        agent.strategies = vec![
+            StrategySlot {
+                strategy: AgentStrategy::Trading {
+                    risk_level: 5,
+                    min_profit: 0,
+                },
+                allocation_bps: 5_000,
+                enabled: true,
+            },
+            StrategySlot {
+                strategy: AgentStrategy::MarketMaker {
+                    spread_bps: 30,
+                    liquidity_depth: 1_000,
+                },
+                allocation_bps: 5_000,
+                enabled: false,
+            },
+        ];
+        assert!(agent.enabled_strategy("Trading").is_some());
+        assert!(agent.strategy_slot("MarketMaker").is_some());
+        assert!(agent.enabled_strategy("MarketMaker").is_none());
+        assert!(agent.enabled_strategy("Oracle").is_none());
     }
 }
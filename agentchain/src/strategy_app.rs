@@ -0,0 +1,35 @@
+use linera_sdk::base::ContractAbi;
+use serde::{Deserialize, Serialize};
+
+/// Interface that user-deployed strategy applications implement so that AgentChain can
+/// consult them on behalf of `AgentStrategy::Custom` agents.
+pub struct CustomStrategyAbi;
+
+impl ContractAbi for CustomStrategyAbi {
+    type Operation = StrategyRequest;
+    type Response = StrategyDecision;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StrategyRequest {
+    /// Asks whether the agent takes a service request, and at what price.
+    EvaluateRequest {
+        agent_id: String,
+        schema_id: String,
+        config_blob: Vec<u8>,
+        requester_agent: String,
+        service_type: String,
+        parameters: String,
+        asset: String,
+        payment: u128,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrategyDecision {
+    pub accept: bool,
+    /// Why the request was declined.
+    pub reason: Option<String>,
+    /// Lowest payment the agent takes for the request, if it prices it.
+    pub price: Option<u128>,
+}
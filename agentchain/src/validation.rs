@@ -0,0 +1,323 @@
+use linera_sdk::views::ViewStorageContext;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeSet;
+
+use crate::state::{AgentChainError, AgentChainState, AgentStrategy, StrategySlot};
+
+/// Allowed ranges for strategy parameters. All bounds are inclusive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrategyBounds {
+    /// `Trading::risk_level` must lie in `min_risk_level..=max_risk_level`.
+    pub min_risk_level: u8,
+    pub max_risk_level: u8,
+    /// `Oracle::data_sources` must name between one and this many sources.
+    pub max_data_sources: usize,
+    /// `Oracle::update_frequency`, in seconds.
+    pub min_update_frequency: u64,
+    pub max_update_frequency: u64,
+    /// `Governance::voting_power` an agent may declare.
+    pub min_voting_power: u128,
+    pub max_voting_power: u128,
+    /// `MarketMaker::spread_bps`.
+    pub min_spread_bps: u16,
+    pub max_spread_bps: u16,
+    /// Smallest `MarketMaker::liquidity_depth`.
+    pub min_liquidity_depth: u128,
+    /// Largest `Custom::config_blob`, in bytes.
+    pub max_config_blob_bytes: usize,
+}
+
+impl Default for StrategyBounds {
+    fn default() -> Self {
+        StrategyBounds {
+            min_risk_level: 1,
+            max_risk_level: 10,
+            max_data_sources: 16,
+            min_update_frequency: 10,
+            max_update_frequency: 86_400,
+            min_voting_power: 1,
+            max_voting_power: 1_000_000,
+            min_spread_bps: 1,
+            max_spread_bps: 5_000,
+            min_liquidity_depth: 1,
+            max_config_blob_bytes: 4_096,
+        }
+    }
+}
+
+fn invalid(field: &str, reason: String) -> AgentChainError {
+    AgentChainError::InvalidStrategy {
+        field: field.to_string(),
+        reason,
+    }
+}
+
+fn check_range<T: PartialOrd + std::fmt::Display>(
+    field: &str,
+    value: T,
+    min: T,
+    max: T,
+) -> Result<(), AgentChainError> {
+    if value < min || value > max {
+        return Err(invalid(
+            field,
+            format!("{} is outside the allowed range {}..={}", value, min, max),
+        ));
+    }
+    Ok(())
+}
+
+impl StrategyBounds {
+    pub fn validate(&self, strategy: &AgentStrategy) -> Result<(), AgentChainError> {
+        match strategy {
+            AgentStrategy::Trading { risk_level, .. } => check_range(
+                "risk_level",
+                *risk_level,
+                self.min_risk_level,
+                self.max_risk_level,
+            ),
+            AgentStrategy::Oracle {
+                data_sources,
+                update_frequency,
+            } => {
+                if data_sources.is_empty() {
+                    return Err(invalid("data_sources", "must not be empty".to_string()));
+                }
+                if data_sources.len() > self.max_data_sources {
+                    return Err(invalid(
+                        "data_sources",
+                        format!("at most {} sources are allowed", self.max_data_sources),
+                    ));
+                }
+                if data_sources.iter().any(|source| source.trim().is_empty()) {
+                    return Err(invalid("data_sources", "sources must not be blank".to_string()));
+                }
+                check_range(
+                    "update_frequency",
+                    *update_frequency,
+                    self.min_update_frequency,
+                    self.max_update_frequency,
+                )
+            }
+            AgentStrategy::Governance { voting_power, .. } => check_range(
+                "voting_power",
+                *voting_power,
+                self.min_voting_power,
+                self.max_voting_power,
+            ),
+            AgentStrategy::MarketMaker {
+                spread_bps,
+                liquidity_depth,
+            } => {
+                check_range(
+                    "spread_bps",
+                    *spread_bps,
+                    self.min_spread_bps,
+                    self.max_spread_bps,
+                )?;
+                if *liquidity_depth < self.min_liquidity_depth {
+                    return Err(invalid(
+                        "liquidity_depth",
+                        format!("must be at least {}", self.min_liquidity_depth),
+                    ));
+                }
+                Ok(())
+            }
+            AgentStrategy::Custom {
+                config_blob,
+                schema_id,
+                ..
+            } => {
+                if schema_id.trim().is_empty() {
+                    return Err(invalid("schema_id", "must not be empty".to_string()));
+                }
+                if config_blob.len() > self.max_config_blob_bytes {
+                    return Err(invalid(
+                        "config_blob",
+                        format!("at most {} bytes are allowed", self.max_config_blob_bytes),
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks every slot of an agent, that no strategy kind appears twice and that the
+    /// allocations add up to at most 100%.
+    pub fn validate_slots(&self, slots: &[StrategySlot]) -> Result<(), AgentChainError> {
+        if slots.is_empty() {
+            return Err(invalid("strategies", "at least one strategy is required".to_string()));
+        }
+        let mut kinds = BTreeSet::new();
+        let mut total_allocation = 0u32;
+        for slot in slots {
+            self.validate(&slot.strategy)?;
+            if !kinds.insert(slot.strategy.kind()) {
+                return Err(invalid(
+                    "strategies",
+                    format!("{} appears more than once", slot.strategy.kind()),
+                ));
+            }
+            total_allocation += slot.allocation_bps as u32;
+        }
+        if total_allocation > 10_000 {
+            return Err(invalid(
+                "allocation_bps",
+                format!("allocations add up to {} bps, above 10000", total_allocation),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<C: ViewStorageContext> AgentChainState<C> {
+    /// Checks `strategy` against the bounds set at instantiation.
+    pub fn validate_strategy(&self, strategy: &AgentStrategy) -> Result<(), AgentChainError> {
+        self.config.get().strategy_bounds.validate(strategy)
+    }
+
+    /// Checks every slot of an agent against the bounds set at instantiation.
+    pub fn validate_strategies(&self, slots: &[StrategySlot]) -> Result<(), AgentChainError> {
+        self.config.get().strategy_bounds.validate_slots(slots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected_field(bounds: &StrategyBounds, strategy: AgentStrategy) -> String {
+        match bounds.validate(&strategy) {
+            Err(AgentChainError::InvalidStrategy { field, .. }) => field,
+            other => panic!("expected an invalid strategy, got {:?}", other),
+        }
+    }
+
+    fn rejected_slots(bounds: &StrategyBounds, slots: &[StrategySlot]) -> String {
+        match bounds.validate_slots(slots) {
+            Err(AgentChainError::InvalidStrategy { field, .. }) => field,
+            other => panic!("expected invalid slots, got {:?}", other),
+        }
+    }
+
+    fn slot(strategy: AgentStrategy, allocation_bps: u16) -> StrategySlot {
+        StrategySlot {
+            strategy,
+            allocation_bps,
+            enabled: true,
+        }
+    }
+
+    fn trading() -> AgentStrategy {
+        AgentStrategy::Trading {
+            risk_level: 5,
+            min_profit: 0,
+        }
+    }
+
+    fn market_maker() -> AgentStrategy {
+        AgentStrategy::MarketMaker {
+            spread_bps: 30,
+            liquidity_depth: 1_000,
+        }
+    }
+
+    #[test]
+    fn trading_risk_level_bounds_are_inclusive() {
+        let bounds = StrategyBounds::default();
+        for risk_level in [1, 10] {
+            let strategy = AgentStrategy::Trading {
+                risk_level,
+                min_profit: 0,
+            };
+            assert!(bounds.validate(&strategy).is_ok());
+        }
+        for risk_level in [0, 11] {
+            let strategy = AgentStrategy::Trading {
+                risk_level,
+                min_profit: 0,
+            };
+            assert_eq!(rejected_field(&bounds, strategy), "risk_level");
+        }
+    }
+
+    #[test]
+    fn oracle_sources_must_be_named_and_bounded() {
+        let bounds = StrategyBounds {
+            max_data_sources: 2,
+            ..StrategyBounds::default()
+        };
+        let oracle = |sources: &[&str], update_frequency| AgentStrategy::Oracle {
+            data_sources: sources.iter().map(|source| source.to_string()).collect(),
+            update_frequency,
+        };
+
+        assert!(bounds.validate(&oracle(&["a", "b"], 10)).is_ok());
+        assert_eq!(rejected_field(&bounds, oracle(&[], 10)), "data_sources");
+        assert_eq!(rejected_field(&bounds, oracle(&["a", "b", "c"], 10)), "data_sources");
+        assert_eq!(rejected_field(&bounds, oracle(&["a", " "], 10)), "data_sources");
+        assert_eq!(rejected_field(&bounds, oracle(&["a"], 9)), "update_frequency");
+        assert_eq!(rejected_field(&bounds, oracle(&["a"], 86_401)), "update_frequency");
+    }
+
+    #[test]
+    fn governance_and_market_maker_bounds_are_enforced() {
+        let bounds = StrategyBounds::default();
+        let governance = |voting_power| AgentStrategy::Governance {
+            voting_power,
+            delegation_enabled: false,
+        };
+        assert!(bounds.validate(&governance(1_000_000)).is_ok());
+        assert_eq!(rejected_field(&bounds, governance(0)), "voting_power");
+        assert_eq!(rejected_field(&bounds, governance(1_000_001)), "voting_power");
+
+        let market_maker = |spread_bps, liquidity_depth| AgentStrategy::MarketMaker {
+            spread_bps,
+            liquidity_depth,
+        };
+        assert!(bounds.validate(&market_maker(5_000, 1)).is_ok());
+        assert_eq!(rejected_field(&bounds, market_maker(0, 1)), "spread_bps");
+        assert_eq!(rejected_field(&bounds, market_maker(5_001, 1)), "spread_bps");
+        assert_eq!(rejected_field(&bounds, market_maker(100, 0)), "liquidity_depth");
+    }
+
+    #[test]
+    fn slots_may_mix_kinds_within_the_full_allocation() {
+        let bounds = StrategyBounds::default();
+        let slots = [slot(trading(), 6_000), slot(market_maker(), 4_000)];
+        assert!(bounds.validate_slots(&slots).is_ok());
+    }
+
+    #[test]
+    fn slots_must_exist_differ_in_kind_and_fit_the_allocation() {
+        let bounds = StrategyBounds::default();
+        assert_eq!(rejected_slots(&bounds, &[]), "strategies");
+        assert_eq!(
+            rejected_slots(&bounds, &[slot(trading(), 1_000), slot(trading(), 1_000)]),
+            "strategies"
+        );
+        assert_eq!(
+            rejected_slots(&bounds, &[slot(trading(), 6_000), slot(market_maker(), 4_001)]),
+            "allocation_bps"
+        );
+        // Allocations cannot overflow their sum.
+        assert_eq!(
+            rejected_slots(&bounds, &[slot(trading(), u16::MAX), slot(market_maker(), u16::MAX)]),
+            "allocation_bps"
+        );
+    }
+
+    #[test]
+    fn every_slot_is_checked_against_the_bounds() {
+        let bounds = StrategyBounds::default();
+        let risky = AgentStrategy::Trading {
+            risk_level: 11,
+            min_profit: 0,
+        };
+        assert_eq!(
+            rejected_slots(&bounds, &[slot(market_maker(), 0), slot(risky, 0)]),
+            "risk_level"
+        );
+    }
+}